@@ -57,7 +57,7 @@ fn get_gamma_rate(report: &[u32], width: u32) -> u32 {
 /// A field is positive if there were more 1's than 0's
 /// Order is big-endian (Vec[0] indicates the 1's place of the binary)
 fn accumulate_bit_counts(width: u32, report: &[u32]) -> Vec<i32> {
-    let mut accumulator = Vec::from_iter(std::iter::repeat(0).take(width as usize));
+    let mut accumulator = vec![0; width as usize];
     for line in report {
         for (i, bit) in accumulator.iter_mut().enumerate() {
             *bit += match line >> i & 1 {
@@ -1,6 +1,6 @@
 use std::fs;
 
-use itertools::{enumerate, Itertools};
+use advent_of_code::day_4::{parse, simulate_boards, WinPattern};
 
 fn main() {
     let filename = "day4_input.txt";
@@ -18,135 +18,13 @@ fn do_the_thing(input: &str) -> u32 {
     println!("Sequence: {:?}", sequence);
     println!("Boards: {:?}", boards);
 
-    let (winning_number, unmarked_sum) = simulate_boards(&sequence, &mut boards);
-
-    winning_number * unmarked_sum
-}
-
-fn simulate_boards(seq: &[u32], boards: &mut [Board]) -> (u32, u32) {
-    let mut winning_number = 0;
-    let mut unmarked_sum = 0;
-    let mut winner_tracker = Vec::new();
-    for number in seq {
-        println!("Number called: {}", number);
-
-        for (i, board) in boards.iter_mut().enumerate() {
-            let wins = board.mark(*number);
-            // println!("Boards: {:?}", board);
-            if wins {
-                if winner_tracker.contains(&i) {
-                    continue;
-                } else {
-                    winner_tracker.push(i);
-
-                    // Update the latest winner
-                    winning_number = *number;
-                    unmarked_sum = board.unmarked_sum();
-
-                    // println!("Winning Board: {:?}", board);
-                    println!("Results: {} {}", winning_number, unmarked_sum);
-                }
-            }
-        }
-    }
-    (winning_number, unmarked_sum)
-}
-
-fn parse(input: &str) -> (Vec<u32>, Vec<Board>) {
-    let mut lines = input.lines();
-    let seq = if let Some(l) = lines.next() {
-        l.split_terminator(',')
-            .map(|s| s.parse::<u32>().unwrap())
-            .collect_vec()
-    } else {
-        panic!("Malformed first line!");
-    };
-
-    let mut boards = Vec::new();
-
-    let mut counter = 0;
-    let mut board = Board::default();
-
-    for line in lines {
-        println!("{:?}", line);
-        if line.chars().count() > 1 {
-            // Line has data
-            let result = line
-                .split_whitespace()
-                .map(|s| s.parse::<u32>().unwrap())
-                .map(|n| (n, false))
-                .collect_vec();
-            for (i, item) in enumerate(result) {
-                board.board_numbers[5 * counter + i] = item;
-            }
-            counter += 1;
-        }
-
-        if counter == 5 {
-            // We have completed a board:
-            boards.push(board);
-            board = Board::default();
-            counter = 0;
-        }
+    let timeline = simulate_boards(&sequence, &mut boards, &WinPattern::standard());
+    for win in timeline.wins.iter() {
+        println!("Winning Board: {:?}", win);
     }
-    (seq, boards)
-}
+    println!("Boards that never won: {:?}", timeline.never_won);
 
-#[derive(Debug, Default)]
-struct Board {
-    board_numbers: [(u32, bool); 25],
-}
-
-impl Board {
-    fn unmarked_sum(&self) -> u32 {
-        let sum = self
-            .board_numbers
-            .iter()
-            .filter(|(_, x)| !x)
-            .map(|x| x.0)
-            .sum();
-        sum
-    }
-
-    fn mark(&mut self, number: u32) -> bool {
-        let found = self.board_numbers.iter().find_position(|x| x.0 == number);
-
-        match found {
-            Some((x, _)) => {
-                self.board_numbers[x].1 = true;
-                Self::check_wins(self.board_numbers, x)
-            }
-            None => false,
-        }
-    }
-
-    fn check_wins(board_numbers: [(u32, bool); 25], position: usize) -> bool {
-        // We get a clue for what cases to check based on the position.
-        let (x, y) = (position % 5, position / 5);
-        println!("Evaluating ({}, {})", x, y);
-        // check row (is contiguous)
-        let mut count = 0;
-        let row = board_numbers
-            .iter()
-            .enumerate()
-            .filter(|&(i, _)| i / 5 == y)
-            .map(|(_, &(_, x))| {
-                // println!("{}", x);
-                count += 1;
-                x
-            })
-            .all(|x| x);
-        println!("Row count {}", count);
-        // check col (is not contiguous)
-        let col = board_numbers
-            .iter()
-            .enumerate()
-            .filter(|&(i, _)| i % 5 == x)
-            .map(|(_, &(_, x))| x)
-            .all(|x| x);
-        println!("{} {} -> {}", row, col, row || col);
-        row || col
-    }
+    timeline.last().map_or(0, |win| win.score)
 }
 
 #[cfg(test)]
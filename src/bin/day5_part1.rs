@@ -143,12 +143,10 @@ impl Grid {
 
 impl std::fmt::Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut output = std::iter::repeat(
-            std::iter::repeat(0)
-                .take(self.width as usize)
-                .collect::<Vec<u32>>(),
+        let mut output = std::iter::repeat_n(
+            std::iter::repeat_n(0, self.width as usize).collect::<Vec<u32>>(),
+            self.height as usize,
         )
-        .take(self.height as usize)
         .collect::<Vec<Vec<u32>>>();
         for (point, &value) in self.grid.iter() {
             // println!("x: {}, y: {}, output: {:?}", point.x, point.y, output);
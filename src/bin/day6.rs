@@ -1,3 +1,7 @@
+use advent_of_code::counting::{self, Count, CountError};
+use advent_of_code::matrix::Matrix;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
 use std::fs;
 
 fn main() {
@@ -6,51 +10,315 @@ fn main() {
 
     println!("{}", contents);
 
-    let result_part1 = simulate_fish(&contents, 80);
+    for days in [80, 256, 600] {
+        match count_fish(&contents, days) {
+            Ok(result) => println!("Result ({} days): {}", days, result),
+            Err(error) => println!("Error ({} days): {}", days, error),
+        }
+    }
+
+    let modulus = BigUint::from(1_000_000_007u32);
+    let days = 1_000_000_000_000;
+    let result_far = fish_after(&contents, days, Some(&modulus));
+
+    println!("Result ({} days, mod {}): {}", days, modulus, result_far);
+}
+
+/// How a species of fish reproduces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Species {
+    newborn_timer: usize, // Timer of a newly spawned fish.
+    reset_timer: usize,   // Timer of a fish after it spawns.
+}
 
-    println!("Result ({} days): {}", 80, result_part1);
+impl Species {
+    const LANTERNFISH: Species = Species {
+        newborn_timer: 8,
+        reset_timer: 6,
+    };
 
-    let result_part2 = simulate_fish(&contents, 256);
+    fn buckets(&self) -> usize {
+        self.newborn_timer.max(self.reset_timer) + 1
+    }
+}
 
-    println!("Result ({} days): {}", 256, result_part2);
+/// Counts of fish by timer, for each species.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Population<T = u128> {
+    species: Vec<Species>,
+    groups: Vec<Vec<T>>, // groups[species][timer]
 }
 
-fn simulate_fish(input: &str, max_days: u32) -> u128 {
+impl<T: Count> Population<T> {
+    fn new(species: Vec<Species>) -> Self {
+        let groups = species
+            .iter()
+            .map(|species| vec![T::zero(); species.buckets()])
+            .collect();
+
+        Self { species, groups }
+    }
+
+    fn add(&mut self, species: usize, timer: usize, count: T) -> Result<(), CountError> {
+        let group = &mut self.groups[species][timer];
+        *group = counting::add(group, &count)?;
+
+        Ok(())
+    }
+
+    /// Advances one day.
+    fn step(&mut self) -> Result<(), CountError> {
+        for (species, groups) in self.species.iter().zip(self.groups.iter_mut()) {
+            let spawning = groups[0].clone();
+            groups.rotate_left(1);
+            *groups.last_mut().unwrap() = T::zero();
+            for timer in [species.newborn_timer, species.reset_timer] {
+                groups[timer] = counting::add(&groups[timer], &spawning)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The population on every day from today (index 0) until `days` from now.
+    fn history(&self, days: u32) -> Result<Vec<Self>, CountError> {
+        let mut population = self.clone();
+        let mut history = vec![population.clone()];
+        for _ in 0..days {
+            population.step()?;
+            history.push(population.clone());
+        }
+
+        Ok(history)
+    }
+
+    fn species_total(&self, species: usize) -> Result<T, CountError> {
+        counting::sum(&self.groups[species])
+    }
+
+    fn total(&self) -> Result<T, CountError> {
+        (0..self.species.len()).try_fold(T::zero(), |total, species| {
+            counting::add(&total, &self.species_total(species)?)
+        })
+    }
+
+    /// Totals per species after `days`, by raising each species' transition matrix.
+    ///
+    /// With a modulus the counts are reduced as they go, so `days` can be huge.
+    fn totals_after(&self, days: u64, modulus: Option<&BigUint>) -> Vec<BigUint> {
+        self.species
+            .iter()
+            .zip(self.groups.iter())
+            .map(|(species, groups)| {
+                let groups = groups.iter().map(Count::to_biguint).collect::<Vec<_>>();
+                let counts = transition_matrix(species)
+                    .pow(days, modulus)
+                    .apply(&groups, modulus);
+
+                let total = counts.into_iter().sum::<BigUint>();
+                match modulus {
+                    Some(modulus) => total % modulus,
+                    None => total,
+                }
+            })
+            .collect()
+    }
+}
+
+/// One day of `Population::step` for a single species, as a matrix.
+fn transition_matrix(species: &Species) -> Matrix {
+    let size = species.buckets();
+    let mut cells = vec![vec![BigUint::zero(); size]; size];
+    for (timer, row) in cells.iter_mut().enumerate().take(size - 1) {
+        row[timer + 1] = BigUint::one();
+    }
+    cells[species.newborn_timer][0] += 1u32;
+    cells[species.reset_timer][0] += 1u32;
+
+    Matrix { cells }
+}
+
+fn parse_population<T: Count>(input: &str) -> Population<T> {
     let fish = input
         .split_terminator(',')
-        .map(|s| s.parse::<u32>().unwrap());
-    let mut groups = [0; 9];
+        .map(|s| s.trim().parse::<usize>().unwrap());
+    let mut population = Population::new(vec![Species::LANTERNFISH]);
     for f in fish {
-        groups[f as usize] += 1;
+        // A single fish can't overflow any count type.
+        population.add(0, f, T::one()).unwrap();
     }
 
-    for day in 0..max_days {
-        let tmp = groups[0];
-        groups.rotate_left(1);
-        groups[6] += tmp;
-        println!("Count after day {}: {:?}", day, groups);
+    population
+}
+
+/// Count of fish after `days`, without simulating each day.
+fn fish_after(input: &str, days: u64, modulus: Option<&BigUint>) -> BigUint {
+    let totals = parse_population::<u128>(input).totals_after(days, modulus);
+
+    totals.into_iter().next().unwrap()
+}
+
+fn simulate_fish<T: Count>(input: &str, max_days: u32) -> Result<T, CountError> {
+    let population = parse_population::<T>(input);
+
+    let history = population.history(max_days)?;
+    for (day, population) in history.iter().enumerate().skip(1) {
+        println!("Count after day {}: {:?}", day - 1, population.groups[0]);
     }
 
-    groups.iter().sum::<_>()
+    history.last().unwrap().total()
+}
+
+/// Simulates with u128 counts, switching to big integers if they overflow.
+fn count_fish(input: &str, max_days: u32) -> Result<BigUint, CountError> {
+    counting::with_fallback(
+        || simulate_fish::<u128>(input, max_days),
+        || simulate_fish::<BigUint>(input, max_days),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     #[test]
     fn test_example_part1() {
         let example_string = "3,4,3,1,2";
         println!("{}", example_string);
-        let result = simulate_fish(example_string, 80);
-        assert_eq!(5934, result);
+        let result = simulate_fish::<u128>(example_string, 80);
+        assert_eq!(Ok(5934), result);
     }
 
     #[test]
     fn test_example_part2() {
         let example_string = "3,4,3,1,2";
         println!("{}", example_string);
-        let result = simulate_fish(example_string, 256);
-        assert_eq!(26984457539, result);
+        let result = simulate_fish::<u128>(example_string, 256);
+        assert_eq!(Ok(26984457539), result);
+    }
+
+    #[test]
+    fn test_custom_timers() {
+        // Newborns start on 2 and parents reset to 1.
+        let mut population = Population::new(vec![Species {
+            newborn_timer: 2,
+            reset_timer: 1,
+        }]);
+        population.add(0, 0, 1).unwrap();
+
+        let result = population
+            .history(6)
+            .unwrap()
+            .iter()
+            .map(|population| population.total().unwrap())
+            .collect::<Vec<u128>>();
+
+        assert_eq!(vec![1, 2, 2, 3, 4, 5, 7], result);
+    }
+
+    #[test]
+    fn test_multiple_species() {
+        // A species that resets to a longer timer than its newborns, alongside lanternfish.
+        let mut population = Population::new(vec![
+            Species::LANTERNFISH,
+            Species {
+                newborn_timer: 3,
+                reset_timer: 6,
+            },
+        ]);
+        for timer in [3, 4, 3, 1, 2] {
+            population.add(0, timer, 1).unwrap();
+        }
+        population.add(1, 4, 1).unwrap();
+
+        let history = population.history(18).unwrap();
+
+        assert_eq!(
+            vec![1, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3],
+            history[..=10]
+                .iter()
+                .map(|population| population.species_total(1).unwrap())
+                .collect::<Vec<u128>>()
+        );
+        assert_eq!(Ok(26), history[18].species_total(0));
+        assert_eq!(
+            Ok(26 + history[18].species_total(1).unwrap()),
+            history[18].total()
+        );
+    }
+
+    #[test]
+    fn test_matrix_matches_simulation() {
+        let example_string = "3,4,3,1,2";
+        let population = parse_population::<u128>(example_string);
+        let history = population.history(256).unwrap();
+
+        for (day, expected) in history.iter().enumerate() {
+            let result = population.totals_after(day as u64, None);
+            assert_eq!(vec![BigUint::from(expected.total().unwrap())], result);
+        }
+    }
+
+    #[test]
+    fn test_matrix_multiple_species() {
+        let mut population = Population::new(vec![
+            Species::LANTERNFISH,
+            Species {
+                newborn_timer: 3,
+                reset_timer: 6,
+            },
+        ]);
+        population.add(0, 3, 2).unwrap();
+        population.add(1, 4, 1).unwrap();
+
+        let expected = population.history(40).unwrap()[40]
+            .groups
+            .iter()
+            .map(|groups| BigUint::from(groups.iter().sum::<u128>()))
+            .collect::<Vec<_>>();
+        let result = population.totals_after(40, None);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_modulo_matches_exact() {
+        let modulus = BigUint::from(1_000_000_007u32);
+        let exact = fish_after("3,4,3,1,2", 1000, None);
+
+        let result = fish_after("3,4,3,1,2", 1000, Some(&modulus));
+
+        assert_eq!(exact % &modulus, result);
+    }
+
+    #[test_case(1_000_000_000_000, 995077479)]
+    #[test_case(1_000_000_000_000_000_000, 860170227)]
+    fn test_far_future_modulo(days: u64, expected: u32) {
+        let modulus = BigUint::from(1_000_000_007u32);
+
+        let result = fish_after("3,4,3,1,2", days, Some(&modulus));
+
+        assert_eq!(BigUint::from(expected), result);
+    }
+
+    #[test]
+    fn test_overflow_detected() {
+        let result = simulate_fish::<u64>("3,4,3,1,2", 600);
+
+        assert_eq!(Err(CountError::Overflow), result);
+    }
+
+    #[test]
+    fn test_big_counts() {
+        let expected = fish_after("3,4,3,1,2", 1200, None);
+
+        let result = count_fish("3,4,3,1,2", 1200);
+
+        assert_eq!(
+            Err(CountError::Overflow),
+            simulate_fish::<u128>("3,4,3,1,2", 1200)
+        );
+        assert_eq!(Ok(expected), result);
     }
 }
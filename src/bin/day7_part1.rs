@@ -1,6 +1,6 @@
 use std::fs;
 
-use itertools::Itertools;
+use advent_of_code::day_7::{best_alignment, parse_crabs, Linear};
 
 fn main() {
     let filename = "day7_input.txt";
@@ -13,34 +13,13 @@ fn main() {
     println!("Result {}", result);
 }
 
-fn do_the_thing(input: &str) -> u32 {
-    let crabs = input
-        .split_terminator(',')
-        .map(|s| s.parse::<u32>().unwrap())
-        .collect_vec();
-
-    let (mut lower, mut upper) = (0, crabs.iter().max().unwrap().to_owned());
-    let func = |x| {
-        crabs
-            .iter()
-            .map(|&c| (c as i32 - x as i32).abs())
-            .sum::<i32>()
-    };
-    while lower != upper {
-        let left = (lower + upper) / 2;
-        let right = left + 1;
-
-        let slope = func(left) - func(right);
-
-        if slope < 0 {
-            // right was greater than left, so we look left,
-            upper = left;
-        } else {
-            // left was greater than right, so we look right,
-            lower = right;
-        }
-    }
-    func(lower) as u32
+fn do_the_thing(input: &str) -> u64 {
+    let crabs = parse_crabs(input);
+
+    let alignment = best_alignment(&crabs, &Linear);
+    println!("Aligning at position {}", alignment.position);
+
+    alignment.cost
 }
 
 #[cfg(test)]
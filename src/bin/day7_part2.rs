@@ -1,6 +1,8 @@
 use std::fs;
 
-use itertools::Itertools;
+use advent_of_code::day_7::{
+    best_alignment, cost_curve, cost_curve_csv, parse_crabs, tied_optima, top_k, Triangular,
+};
 
 fn main() {
     let filename = "day7_input.txt";
@@ -11,37 +13,24 @@ fn main() {
     let result = do_the_thing(&contents);
 
     println!("Result {}", result);
-}
 
-fn do_the_thing(input: &str) -> u32 {
-    let crabs = input
-        .split_terminator(',')
-        .map(|s| s.parse::<u32>().unwrap())
-        .collect_vec();
-
-    let (mut lower, mut upper) = (0, crabs.iter().max().unwrap().to_owned());
-    let func = |x| {
-        crabs
-            .iter()
-            .map(|&c| (c as i32 - x as i32).abs())
-            .map(|x| (0..=x).sum::<i32>())
-            .sum::<i32>()
-    };
-    while lower != upper {
-        let left = (lower + upper) / 2;
-        let right = left + 1;
-
-        let slope = func(left) - func(right);
-
-        if slope < 0 {
-            // right was greater than left, so we look left,
-            upper = left;
-        } else {
-            // left was greater than right, so we look right,
-            lower = right;
-        }
+    let crabs = parse_crabs(&contents);
+    let curve = cost_curve(&crabs, &Triangular);
+    println!("Tied optima {:?}", tied_optima(&curve));
+    println!("Cheapest 5 {:?}", top_k(&curve, 5));
+
+    if let Some(path) = std::env::args().skip_while(|arg| arg != "--curve").nth(1) {
+        fs::write(path, cost_curve_csv(&crabs)).expect("Curve write error");
     }
-    func(lower) as u32
+}
+
+fn do_the_thing(input: &str) -> u64 {
+    let crabs = parse_crabs(input);
+
+    let alignment = best_alignment(&crabs, &Triangular);
+    println!("Aligning at position {}", alignment.position);
+
+    alignment.cost
 }
 
 #[cfg(test)]
@@ -1,6 +1,14 @@
-use std::{collections::HashMap, fs, iter::once};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+};
 
+use advent_of_code::counting::{self, Count, CountError};
+use advent_of_code::matrix::Matrix;
 use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use regex::Regex;
 
 fn main() {
     let filename = "day14_input.txt";
@@ -8,167 +16,693 @@ fn main() {
 
     println!("{}", contents);
 
-    let result = do_the_thing(&contents, 40);
+    // Pass --chemical to read elements like He and Ne as one symbol.
+    let symbols = if std::env::args().any(|arg| arg == "--chemical") {
+        Symbols::Chemical
+    } else {
+        Symbols::Characters
+    };
 
-    println!("Result {}", result);
+    for iters in [40, 200] {
+        match count_polymer(&contents, iters, symbols) {
+            Ok(result) => println!("Result ({} steps) {}", iters, result),
+            Err(error) => println!("Error ({} steps) {}", iters, error),
+        }
+    }
+
+    if let Err(error) = show_polymer(&contents, 40, symbols) {
+        println!("Error showing the polymer {}", error);
+    }
+
+    let validation = validate(&contents, symbols);
+    if !validation.is_valid() {
+        print!("{}", validation);
+    }
+    let dense = DensePolymer::new(&contents, symbols);
+    let modulus = BigUint::from(1_000_000_007u32);
+    println!(
+        "Elements after 1000000 steps, mod {}: {:?}",
+        modulus,
+        dense.histogram_after(1_000_000, Some(&modulus))
+    );
+
+    match Polymer::<u128>::new(&contents, symbols).find_cycle(100) {
+        Ok(Some(cycle)) => println!("Pair counts repeat {:?}", cycle),
+        Ok(None) => println!("Pair counts keep changing"),
+        Err(error) => println!("Error finding a cycle {}", error),
+    }
 }
 
-fn do_the_thing(input: &str, iters: u8) -> u128 {
-    let mut poly = Polymer::new(input);
+fn do_the_thing<T: Count>(input: &str, iters: u8, symbols: Symbols) -> Result<T, CountError> {
+    let mut poly = Polymer::new(input, symbols);
 
     for i in 0..iters {
-        poly = poly.iterate();
+        poly = poly.iterate()?;
         println!("Processing step {}", i + 1);
     }
+    println!("Elements {:?}", poly.histogram());
     poly.get_result()
 }
 
+/// Shows the polymer itself after a few steps, and the middle element after `steps`.
+fn show_polymer(input: &str, steps: usize, symbols: Symbols) -> Result<(), CountError> {
+    let expansion = Expansion::new(input, symbols, steps)?;
+    for step in 0..=4 {
+        println!("Step {}: {}", step, expansion.elements(step).join(""));
+    }
+
+    let middle = expansion.len(steps)? / 2;
+    println!(
+        "Element {} after {} steps: {:?}",
+        middle,
+        steps,
+        expansion.nth_char(steps, middle)
+    );
+    Ok(())
+}
+
+/// Counts with u128, switching to big integers if they overflow.
+fn count_polymer(input: &str, iters: u8, symbols: Symbols) -> Result<BigUint, CountError> {
+    counting::with_fallback(
+        || do_the_thing::<u128>(input, iters, symbols),
+        || do_the_thing::<BigUint>(input, iters, symbols),
+    )
+}
+
+/// An element symbol, like `N` or `He`.
+type Element = String;
+
+/// How the elements in the input are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbols {
+    /// Every character is an element, in any script. `HeNNe` is `H`, `e`, `N`, `N`, `e`.
+    Characters,
+    /// Chemical symbols, where lowercase letters carry on the element before them. `HeNNe` is
+    /// `He`, `N`, `Ne`.
+    Chemical,
+}
+
+impl Symbols {
+    fn parse_elements(self, symbols: &str) -> Vec<Element> {
+        let mut elements: Vec<Element> = Vec::new();
+        for c in symbols.chars() {
+            match elements.last_mut() {
+                Some(element) if self == Symbols::Chemical && c.is_lowercase() => element.push(c),
+                _ => elements.push(c.to_string()),
+            }
+        }
+        elements
+    }
+}
+
+/// Reads rules like `CH -> B`.
+struct RuleParser {
+    symbols: Symbols,
+    rule_regex: Regex,
+}
+
+impl RuleParser {
+    fn new(symbols: Symbols) -> Self {
+        Self {
+            symbols,
+            rule_regex: Regex::new(r#"^\s*(\S+) -> (\S+)\s*$"#).unwrap(),
+        }
+    }
+
+    /// Reads a rule, or None if the line isn't one.
+    fn parse(&self, line: &str) -> Option<((Element, Element), Element)> {
+        let cap = self.rule_regex.captures(line)?;
+        let (left, right) = self
+            .symbols
+            .parse_elements(&cap[1])
+            .into_iter()
+            .collect_tuple()?;
+        let (insert_result,) = self
+            .symbols
+            .parse_elements(&cap[2])
+            .into_iter()
+            .collect_tuple()?;
+        Some(((left, right), insert_result))
+    }
+}
+
+/// Reads the template and the element each pair inserts.
+///
+/// Lines that aren't rules are skipped, and the last of any conflicting rules wins. Use
+/// `validate` to find out about those.
+fn parse_input(
+    input: &str,
+    symbols: Symbols,
+) -> (Vec<Element>, HashMap<(Element, Element), Element>) {
+    let parser = RuleParser::new(symbols);
+    let mut lines = input.lines();
+    let template = symbols.parse_elements(lines.next().unwrap().trim());
+    let insertions = lines.filter_map(|line| parser.parse(line)).collect();
+
+    (template, insertions)
+}
+
+/// Problems with a puzzle input that `parse_input` would otherwise gloss over.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Validation {
+    unparseable: Vec<(usize, String)>, // Line number, from 1, and the line.
+    duplicates: Vec<(Element, Element)>, // Pairs with the same rule given more than once.
+    conflicts: Vec<((Element, Element), Vec<Element>)>, // Pairs with different rules.
+    missing_rules: Vec<(Element, Element)>, // Pairs that can appear but have no rule.
+    unreachable: Vec<Element>,         // Elements in the rules that can never appear.
+}
+
+impl Validation {
+    fn is_valid(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::fmt::Display for Validation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (number, line) in self.unparseable.iter() {
+            writeln!(f, "line {} is not a rule: {:?}", number, line)?;
+        }
+        for (left, right) in self.duplicates.iter() {
+            writeln!(f, "{}{} has a duplicate rule", left, right)?;
+        }
+        for ((left, right), inserts) in self.conflicts.iter() {
+            writeln!(
+                f,
+                "{}{} has conflicting rules: {}",
+                left,
+                right,
+                inserts.join(", ")
+            )?;
+        }
+        for (left, right) in self.missing_rules.iter() {
+            writeln!(f, "{}{} can appear but has no rule", left, right)?;
+        }
+        for element in self.unreachable.iter() {
+            writeln!(f, "{} can never appear", element)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every pair that can come out of the template.
+fn reachable_pairs(
+    template: &[Element],
+    insertions: &HashMap<(Element, Element), Element>,
+) -> HashSet<(Element, Element)> {
+    let mut reachable = template
+        .iter()
+        .cloned()
+        .tuple_windows()
+        .collect::<HashSet<_>>();
+    let mut to_visit = reachable.iter().cloned().collect_vec();
+    while let Some((left, right)) = to_visit.pop() {
+        if let Some(inserted) = insertions.get(&(left.clone(), right.clone())) {
+            for pair in [(left, inserted.clone()), (inserted.clone(), right)] {
+                if reachable.insert(pair.clone()) {
+                    to_visit.push(pair);
+                }
+            }
+        }
+    }
+    reachable
+}
+
+fn validate(input: &str, symbols: Symbols) -> Validation {
+    let parser = RuleParser::new(symbols);
+    let mut validation = Validation::default();
+    let mut rules: HashMap<(Element, Element), Vec<Element>> = HashMap::new();
+    for (number, line) in input.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parser.parse(line) {
+            Some((pair, insert_result)) => rules.entry(pair).or_default().push(insert_result),
+            None => validation.unparseable.push((number + 1, line.to_string())),
+        }
+    }
+
+    for (pair, inserts) in rules.iter().sorted() {
+        if inserts.iter().all_equal() {
+            if inserts.len() > 1 {
+                validation.duplicates.push(pair.clone());
+            }
+        } else {
+            let inserts = inserts.iter().unique().cloned().collect();
+            validation.conflicts.push((pair.clone(), inserts));
+        }
+    }
+
+    let (template, insertions) = parse_input(input, symbols);
+    let reachable = reachable_pairs(&template, &insertions);
+    validation.missing_rules = reachable
+        .iter()
+        .filter(|pair| !insertions.contains_key(pair))
+        .cloned()
+        .sorted()
+        .collect();
+
+    let appearing = reachable
+        .into_iter()
+        .flat_map(|(left, right)| [left, right])
+        .chain(template)
+        .collect::<HashSet<_>>();
+    validation.unreachable = rules
+        .into_iter()
+        .flat_map(|((left, right), inserts)| inserts.into_iter().chain([left, right]))
+        .filter(|element| !appearing.contains(element))
+        .unique()
+        .sorted()
+        .collect();
+
+    validation
+}
+
+/// Where the pair counts start repeating, and how often.
+#[derive(Debug, PartialEq, Eq)]
+struct Cycle {
+    start: usize,
+    period: usize,
+}
+
 #[derive(Debug)]
-struct Polymer {
-    rules: HashMap<(char, char), PolymerPair>,
-    start: char,
-    end: char,
+struct Polymer<T = u128> {
+    rules: HashMap<(Element, Element), PolymerPair<T>>,
+    elements: HashMap<Element, T>, // How many of each element are in the polymer.
 }
 
 // Defines how a pair inserts, as well as track counts.
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-struct PolymerPair {
-    pair: (char, char),
-    insert_result: Option<char>,
-    count: u128,
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct PolymerPair<T = u128> {
+    pair: (Element, Element),
+    insert_result: Option<Element>,
+    count: T,
 }
 
-impl PolymerPair {
+impl<T: Count> PolymerPair<T> {
     /// Expands this polymer pair into the left and right pairs.
     /// Returns None if this pair doesn't expand.
-    fn expand(&self) -> Option<((char, char), (char, char))> {
-        if self.insert_result.is_some() {
-            let left = (self.pair.0, self.insert_result.unwrap());
-            let right = (self.insert_result.unwrap(), self.pair.1);
-            Some((left, right))
-        } else {
-            None
-        }
+    fn expand(&self) -> Option<((Element, Element), (Element, Element))> {
+        self.insert_result.as_ref().map(|insert_result| {
+            let left = (self.pair.0.clone(), insert_result.clone());
+            let right = (insert_result.clone(), self.pair.1.clone());
+            (left, right)
+        })
     }
 
     // increments this pair's count
-    fn increment(&mut self, inc: u128) {
-        self.count += inc;
+    fn increment(&mut self, inc: &T) -> Result<(), CountError> {
+        self.count = counting::add(&self.count, inc)?;
+        Ok(())
     }
 
     // decrements this pair's count
-    fn decrement(&mut self, dec: u128) {
-        self.count -= dec;
+    fn decrement(&mut self, dec: &T) -> Result<(), CountError> {
+        self.count = counting::sub(&self.count, dec)?;
+        Ok(())
     }
 }
 
-impl Polymer {
-    fn new(input: &str) -> Self {
-        use regex::Regex;
-
-        let mut lines = input.lines();
-        let data = lines.next().unwrap().chars().collect_vec();
-        let start = *data.first().unwrap();
-        let end = *data.last().unwrap();
-        let rule_regex = Regex::new(r#"([A-Z]{1})([A-Z]{1}) -> ([A-Z]{1})"#).unwrap();
-
-        let mut rules = lines
-            .filter_map(|s| rule_regex.captures(s))
-            .map(|cap| {
-                let left = cap.get(1).unwrap().as_str().chars().next().unwrap();
-                let right = cap.get(2).unwrap().as_str().chars().next().unwrap();
-                let insert_result = cap.get(3).unwrap().as_str().chars().next();
+impl<T: Count> Polymer<T> {
+    fn new(input: &str, symbols: Symbols) -> Self {
+        let (data, insertions) = parse_input(input, symbols);
+        let mut rules = insertions
+            .into_iter()
+            .map(|(pair, insert_result)| {
                 (
-                    (left, right),
+                    pair.clone(),
                     PolymerPair {
-                        pair: (left, right),
-                        insert_result,
-                        count: 0,
+                        pair,
+                        insert_result: Some(insert_result),
+                        count: T::zero(),
                     },
                 )
             })
             .collect::<HashMap<_, _>>();
 
-        for (left, right) in data.into_iter().tuple_windows() {
-            if let Some(pair) = rules.get_mut(&(left, right)) {
-                pair.increment(1);
-            };
+        // A handful of elements and pairs in the template can't overflow.
+        let mut elements = HashMap::new();
+        for element in data.iter() {
+            let count = elements.entry(element.clone()).or_insert_with(T::zero);
+            *count = counting::add(count, &T::one()).unwrap();
+        }
+        for pair in data.into_iter().tuple_windows() {
+            Self::update_rules_map(&mut rules, pair, &T::one(), true).unwrap();
         }
 
-        Self { rules, start, end }
+        Self { rules, elements }
     }
 
-    fn iterate(self) -> Self {
-        let mut rules = self.rules.clone();
+    fn iterate(mut self) -> Result<Self, CountError> {
+        // Every pair inserts based on the counts from before this step.
+        let expanding = self
+            .rules
+            .values()
+            .filter_map(|pair| Some((pair.pair.clone(), pair.expand()?, pair.count.clone())))
+            .collect_vec();
+
+        for (pair, (left, right), count) in expanding {
+            // Each of these pairs gets one more of the inserted element.
+            let inserted = self.elements.entry(left.1.clone()).or_insert_with(T::zero);
+            *inserted = counting::add(inserted, &count)?;
 
-        for pair in self.rules.values() {
-            println!("Processing Pair: {:?}, count {}", pair.pair, pair.count);
-            if let Some((left, right)) = pair.expand() {
-                // This pair in the map contributes to increasing the polymer.
-                let count = pair.count;
+            Self::update_rules_map(&mut self.rules, pair, &count, false)?; // We remove each pair from the original count.
+            Self::update_rules_map(&mut self.rules, left, &count, true)?; // We add each new left pair to the count.
+            Self::update_rules_map(&mut self.rules, right, &count, true)?; // We add each new right pair to the count.
+        }
+        Ok(self)
+    }
+
+    /// The pair counts, leaving out pairs that aren't in the polymer.
+    fn pair_counts(&self) -> Vec<((Element, Element), T)> {
+        self.rules
+            .values()
+            .filter(|pair| !pair.count.is_zero())
+            .map(|pair| (pair.pair.clone(), pair.count.clone()))
+            .sorted()
+            .collect()
+    }
 
-                Self::update_rules_map(&mut rules, pair.pair, count, false); // We remove each pair from the original count.
-                Self::update_rules_map(&mut rules, left, count, true); // We add each new left pair to the count.
-                Self::update_rules_map(&mut rules, right, count, true); // We add each new right pair to the count.
+    /// Looks for the pair counts repeating within `max_steps`.
+    ///
+    /// Any pair with a rule grows the polymer, so this only happens once no pair in it has one.
+    fn find_cycle(self, max_steps: usize) -> Result<Option<Cycle>, CountError> {
+        let mut seen = BTreeMap::new();
+        let mut poly = self;
+        for step in 0..=max_steps {
+            if let Some(start) = seen.insert(poly.pair_counts(), step) {
+                return Ok(Some(Cycle {
+                    start,
+                    period: step - start,
+                }));
             }
+            poly = poly.iterate()?;
         }
-        Self { rules, ..self }
+        Ok(None)
     }
 
-    /// Gets the quantity of most common and least commons characters in data, and returns the difference.
-    fn get_result(&self) -> u128 {
-        if let Some((min, max)) = self
-            .rules
+    /// The count of every element in the polymer, most common first.
+    fn histogram(&self) -> Vec<(Element, T)> {
+        self.elements
             .iter()
-            .flat_map(|(&(left, right), pair)| {
-                once((left, pair.count)).chain(once((right, pair.count)))
-            })
-            .sorted_by_key(|&(c, _count)| c)
-            .coalesce(|previous, current| {
-                if previous.0 == current.0 {
-                    // characters match
-                    Ok((previous.0, previous.1 + current.1))
-                } else {
-                    Err((previous, current))
-                }
-            }) // This gets us a (char, u128) iterator of total counts.
-            .map(|(c, count)| {
-                if c == self.start || c == self.end {
-                    (count - 1) / 2 + 1
-                } else {
-                    count / 2
-                }
-            }) // Reduce all character counts by half, with exception of start and end.
-            .minmax()
-            .into_option()
-        {
-            dbg!(max) - dbg!(min)
+            .map(|(element, count)| (element.clone(), count.clone()))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect()
+    }
+
+    /// Gets the quantity of most common and least commons characters in data, and returns the difference.
+    fn get_result(&self) -> Result<T, CountError> {
+        if let Some((min, max)) = self.elements.values().minmax().into_option() {
+            counting::sub(max, min)
         } else {
-            0
+            Ok(T::zero())
         }
     }
 
     fn update_rules_map(
-        rules: &mut HashMap<(char, char), PolymerPair>,
-        pair: (char, char),
-        count: u128,
+        rules: &mut HashMap<(Element, Element), PolymerPair<T>>,
+        pair: (Element, Element),
+        count: &T,
         increment: bool,
-    ) {
-        if let Some(x) = rules.get_mut(&pair) {
-            // We have this rule in our map, we need to adjust it.
-            if increment {
-                x.increment(count);
-            } else {
-                x.decrement(count);
-            }
+    ) -> Result<(), CountError> {
+        // If this rule is not defined, we need to add the pair manually so it keeps its count.
+        let x = rules.entry(pair.clone()).or_insert_with(|| PolymerPair {
+            pair,
+            insert_result: None,
+            count: T::zero(),
+        });
+        if increment {
+            x.increment(count)
         } else {
-            // This rule is not defined, so we need to add the pair manually.
-            let new_pair = PolymerPair {
-                pair,
-                insert_result: None,
-                count: 0,
+            x.decrement(count)
+        }
+    }
+}
+
+/// Pairs numbered 0..k, so that a step is a matrix multiplication and many steps are a power.
+struct DensePolymer {
+    pairs: Vec<(Element, Element)>, // Every pair that can appear, by index.
+    initial: Vec<BigUint>,          // Pair counts in the template.
+    transition: Matrix,             // One step, indexed [to][from].
+    last: Element,                  // Nothing is ever inserted after the last element.
+}
+
+impl DensePolymer {
+    fn new(input: &str, symbols: Symbols) -> Self {
+        let (template, insertions) = parse_input(input, symbols);
+        let pairs = reachable_pairs(&template, &insertions)
+            .into_iter()
+            .sorted()
+            .collect_vec();
+        let index = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, pair)| (pair, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut initial = vec![BigUint::zero(); pairs.len()];
+        for pair in template.iter().cloned().tuple_windows::<(_, _)>() {
+            initial[index[&pair]] += 1u32;
+        }
+
+        let mut cells = vec![vec![BigUint::zero(); pairs.len()]; pairs.len()];
+        for (from, (left, right)) in pairs.iter().enumerate() {
+            match insertions.get(&(left.clone(), right.clone())) {
+                Some(inserted) => {
+                    cells[index[&(left.clone(), inserted.clone())]][from] += 1u32;
+                    cells[index[&(inserted.clone(), right.clone())]][from] += 1u32;
+                }
+                None => cells[from][from] += 1u32,
+            }
+        }
+
+        Self {
+            pairs,
+            initial,
+            transition: Matrix { cells },
+            last: template.last().unwrap().clone(),
+        }
+    }
+
+    /// How many of each pair there are after `steps`, optionally modulo something.
+    fn pair_counts_after(&self, steps: u64, modulus: Option<&BigUint>) -> Vec<BigUint> {
+        self.transition
+            .pow(steps, modulus)
+            .apply(&self.initial, modulus)
+    }
+
+    /// Which pairs are actually in the polymer after `steps`.
+    ///
+    /// Counts taken modulo something can be zero when the pair is there, so this follows the
+    /// pairs themselves, skipping ahead once the same set of pairs comes round again.
+    fn pairs_present_after(&self, steps: u64) -> Vec<bool> {
+        let mut present = self
+            .initial
+            .iter()
+            .map(|count| !count.is_zero())
+            .collect_vec();
+        let mut seen = HashMap::new();
+        for step in 0..steps {
+            if let Some(start) = seen.insert(present.clone(), step) {
+                for _ in 0..(steps - step) % (step - start) {
+                    present = self.step_present(&present);
+                }
+                break;
+            }
+            present = self.step_present(&present);
+        }
+        present
+    }
+
+    fn step_present(&self, present: &[bool]) -> Vec<bool> {
+        self.transition
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(present)
+                    .any(|(cell, &present)| present && !cell.is_zero())
+            })
+            .collect()
+    }
+
+    /// The count of every element in the polymer after `steps`, by element.
+    ///
+    /// Every element but the last is the left of exactly one pair.
+    fn histogram_after(&self, steps: u64, modulus: Option<&BigUint>) -> Vec<(Element, BigUint)> {
+        let mut histogram = BTreeMap::new();
+        histogram.insert(self.last.clone(), BigUint::one());
+        for (((left, _), count), present) in self
+            .pairs
+            .iter()
+            .zip(self.pair_counts_after(steps, modulus))
+            .zip(self.pairs_present_after(steps))
+        {
+            if present {
+                *histogram.entry(left.clone()).or_insert_with(BigUint::zero) += count;
+            }
+        }
+
+        histogram
+            .into_iter()
+            .map(|(element, count)| match modulus {
+                Some(modulus) => (element, count % modulus),
+                None => (element, count),
+            })
+            .collect()
+    }
+}
+
+/// The actual polymer, for looking at rather than counting.
+struct Expansion {
+    template: Vec<Element>,
+    insertions: HashMap<(Element, Element), Element>,
+    /// How many elements end up between each pair, for each step up to the `max_steps` it was
+    /// built with. Pairs without a rule never grow, so they're left out.
+    lengths: Vec<HashMap<(Element, Element), u128>>,
+}
+
+impl Expansion {
+    /// Fails if the length between some pair doesn't fit in a u128 within `max_steps`.
+    fn new(input: &str, symbols: Symbols, max_steps: usize) -> Result<Self, CountError> {
+        let (template, insertions) = parse_input(input, symbols);
+
+        let mut lengths = vec![insertions
+            .keys()
+            .map(|pair| (pair.clone(), 0u128))
+            .collect::<HashMap<_, _>>()];
+        for step in 1..=max_steps {
+            let previous = &lengths[step - 1];
+            let length = |left: &Element, right: &Element| {
+                previous
+                    .get(&(left.clone(), right.clone()))
+                    .copied()
+                    .unwrap_or(0)
             };
-            rules.insert(pair, new_pair);
+            let next = insertions
+                .iter()
+                .map(|((left, right), inserted)| {
+                    let total = counting::add(&length(left, inserted), &1)?;
+                    let total = counting::add(&total, &length(inserted, right))?;
+                    Ok(((left.clone(), right.clone()), total))
+                })
+                .collect::<Result<_, CountError>>()?;
+            lengths.push(next);
+        }
+
+        Ok(Self {
+            template,
+            insertions,
+            lengths,
+        })
+    }
+
+    /// Lazily walks the polymer after `steps`, without building it.
+    fn elements(&self, steps: usize) -> Elements<'_> {
+        let mut stack = Vec::new();
+        for (i, element) in self.template.iter().enumerate().rev() {
+            stack.push(Frame::Element(element));
+            if i > 0 {
+                stack.push(Frame::Between(&self.template[i - 1], element, steps));
+            }
+        }
+
+        Elements {
+            insertions: &self.insertions,
+            stack,
+        }
+    }
+
+    /// How many elements end up between `left` and `right` after `steps`.
+    fn length(&self, left: &Element, right: &Element, steps: usize) -> u128 {
+        assert!(
+            steps < self.lengths.len(),
+            "Lengths are only known up to {} steps",
+            self.lengths.len() - 1
+        );
+        self.lengths[steps]
+            .get(&(left.clone(), right.clone()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// How long the polymer is after `steps`.
+    fn len(&self, steps: usize) -> Result<u128, CountError> {
+        self.template
+            .iter()
+            .tuple_windows()
+            .try_fold(self.template.len() as u128, |total, (left, right)| {
+                counting::add(&total, &self.length(left, right, steps))
+            })
+    }
+
+    /// The element at `index` in the polymer after `steps`, found by skipping over whole pairs.
+    fn nth_char(&self, steps: usize, index: u128) -> Option<&Element> {
+        let first = self.template.first()?;
+        if index == 0 {
+            return Some(first);
+        }
+        let mut index = index - 1;
+
+        for (left, right) in self.template.iter().tuple_windows() {
+            let between = self.length(left, right, steps);
+            if index < between {
+                // It's somewhere in this pair, so narrow it down step by step.
+                let (mut left, mut right) = (left, right);
+                let mut step = steps;
+                loop {
+                    let inserted = &self.insertions[&(left.clone(), right.clone())];
+                    let before = self.length(left, inserted, step - 1);
+                    match index.cmp(&before) {
+                        std::cmp::Ordering::Less => right = inserted,
+                        std::cmp::Ordering::Equal => return Some(inserted),
+                        std::cmp::Ordering::Greater => {
+                            index -= before + 1;
+                            left = inserted;
+                        }
+                    }
+                    step -= 1;
+                }
+            }
+            index -= between;
+            if index == 0 {
+                return Some(right);
+            }
+            index -= 1;
+        }
+
+        // The index is past the end.
+        None
+    }
+}
+
+enum Frame<'a> {
+    Element(&'a Element),
+    Between(&'a Element, &'a Element, usize), // The elements inserted between a pair over some steps.
+}
+
+/// Depth first iterator over an `Expansion`.
+struct Elements<'a> {
+    insertions: &'a HashMap<(Element, Element), Element>,
+    stack: Vec<Frame<'a>>,
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = &'a Element;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Frame::Element(element) => return Some(element),
+                Frame::Between(_, _, 0) => (),
+                Frame::Between(left, right, steps) => {
+                    if let Some(inserted) = self.insertions.get(&(left.clone(), right.clone())) {
+                        self.stack.push(Frame::Between(inserted, right, steps - 1));
+                        self.stack.push(Frame::Element(inserted));
+                        self.stack.push(Frame::Between(left, inserted, steps - 1));
+                    }
+                }
+            }
         }
     }
 }
@@ -177,10 +711,9 @@ impl Polymer {
 mod tests {
     use super::*;
     use indoc::indoc;
+    use test_case::test_case;
 
-    #[test]
-    fn test_example() {
-        let input = indoc! {"
+    const EXAMPLE: &str = indoc! {"
         NNCB
 
         CH -> B
@@ -200,13 +733,331 @@ mod tests {
         CC -> N
         CN -> C"};
 
+    #[test]
+    fn test_example() {
+        let input = EXAMPLE;
+
         // let result = do_the_thing(input, 3);
         // assert_eq!(1, result);
 
-        let result = do_the_thing(input, 10);
-        assert_eq!(1588, result);
+        let result = do_the_thing::<u128>(input, 10, Symbols::Characters);
+        assert_eq!(Ok(1588), result);
+
+        let result = do_the_thing::<u128>(input, 40, Symbols::Characters);
+        assert_eq!(Ok(2188189693529), result);
+    }
+
+    #[test]
+    fn test_overflow_detected() {
+        let result = do_the_thing::<u64>(EXAMPLE, 70, Symbols::Characters);
+
+        assert_eq!(Err(CountError::Overflow), result);
+    }
+
+    #[test]
+    fn test_big_counts() {
+        let expected = "3213876088504849459628933947091293102955756486973947218116623"
+            .parse::<BigUint>()
+            .unwrap();
+
+        let result = count_polymer(EXAMPLE, 200, Symbols::Characters);
+
+        assert_eq!(
+            Err(CountError::Overflow),
+            do_the_thing::<u128>(EXAMPLE, 200, Symbols::Characters)
+        );
+        assert_eq!(Ok(expected), result);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut poly = Polymer::<u128>::new(EXAMPLE, Symbols::Characters);
+        for _ in 0..10 {
+            poly = poly.iterate().unwrap();
+        }
+
+        let result = poly.histogram();
+
+        assert_eq!(
+            vec![
+                ("B".to_string(), 1749),
+                ("N".to_string(), 865),
+                ("C".to_string(), 298),
+                ("H".to_string(), 161),
+            ],
+            result
+        );
+    }
+
+    #[test_case("NNCB", Symbols::Characters, vec!["N", "N", "C", "B"])]
+    #[test_case("HeNNe", Symbols::Characters, vec!["H", "e", "N", "N", "e"])]
+    #[test_case("HeNNe", Symbols::Chemical, vec!["He", "N", "Ne"])]
+    #[test_case("Ω🦀Жж", Symbols::Characters, vec!["Ω", "🦀", "Ж", "ж"])]
+    #[test_case("Ω🦀Жж", Symbols::Chemical, vec!["Ω", "🦀", "Жж"])]
+    fn test_parse_elements(symbols: &str, style: Symbols, expected: Vec<&str>) {
+        let result = style.parse_elements(symbols);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test_case("Ne", "Ca", "Be", "He", Symbols::Chemical)]
+    #[test_case("n", "c", "b", "h", Symbols::Characters ; "lowercase")]
+    #[test_case("Ω", "🦀", "€", "ж", Symbols::Characters)]
+    #[test_case("Ω", "🦀", "€", "Жж", Symbols::Chemical)]
+    fn test_other_elements(n: &str, c: &str, b: &str, h: &str, symbols: Symbols) {
+        let input = EXAMPLE
+            .replace('N', n)
+            .replace('C', c)
+            .replace('B', b)
+            .replace('H', h);
+
+        let result = do_the_thing::<u128>(&input, 10, symbols);
+
+        assert_eq!(Ok(1588), result);
+    }
+
+    #[test_case(1, "NCNBCHB")]
+    #[test_case(2, "NBCCNBBBCBHCB")]
+    #[test_case(3, "NBBBCNCCNBBNBNBBCHBHHBCHB")]
+    fn test_expansion(steps: usize, expected: &str) {
+        let expansion = Expansion::new(EXAMPLE, Symbols::Characters, 0).unwrap();
+
+        let result = expansion.elements(steps).join("");
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_expansion_matches_counts() {
+        let expansion = Expansion::new(EXAMPLE, Symbols::Characters, 12).unwrap();
+        let mut poly = Polymer::<u128>::new(EXAMPLE, Symbols::Characters);
+        for steps in 0..=12 {
+            let mut counts = HashMap::new();
+            for element in expansion.elements(steps) {
+                *counts.entry(element.clone()).or_insert(0) += 1;
+            }
+            let result = counts
+                .into_iter()
+                .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+                .collect_vec();
+
+            assert_eq!(poly.histogram(), result);
+            assert_eq!(
+                poly.histogram()
+                    .iter()
+                    .map(|(_, count)| count)
+                    .sum::<u128>(),
+                expansion.len(steps).unwrap()
+            );
+
+            poly = poly.iterate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_nth_char() {
+        let expansion = Expansion::new(EXAMPLE, Symbols::Characters, 8).unwrap();
+        for steps in 0..=8 {
+            let expected = expansion.elements(steps).collect_vec();
+
+            let result = (0..expected.len() as u128)
+                .map(|index| expansion.nth_char(steps, index).unwrap())
+                .collect_vec();
+
+            assert_eq!(expected, result);
+            assert_eq!(None, expansion.nth_char(steps, expected.len() as u128));
+        }
+    }
+
+    #[test]
+    fn test_nth_char_far() {
+        let expansion = Expansion::new(EXAMPLE, Symbols::Characters, 40).unwrap();
+        let len = expansion.len(40).unwrap();
+
+        assert_eq!(3 * (1 << 40) + 1, len);
+        assert_eq!(Some(&"B".to_string()), expansion.nth_char(40, len - 1));
+        assert_eq!(Some(&"N".to_string()), expansion.nth_char(40, 0));
+    }
+
+    #[test]
+    fn test_expansion_overflow() {
+        // The example polymer doubles in length every step.
+        let result = Expansion::new(EXAMPLE, Symbols::Characters, 130);
+
+        assert_eq!(Err(CountError::Overflow), result.map(|_| ()));
+    }
+
+    #[test]
+    fn test_expansion_len_overflow() {
+        // Each pair fits after 127 steps, but the three of them together don't.
+        let expansion = Expansion::new(EXAMPLE, Symbols::Characters, 127).unwrap();
+
+        assert_eq!(Err(CountError::Overflow), expansion.len(127));
+    }
+
+    #[test]
+    fn test_validate_example() {
+        let result = validate(EXAMPLE, Symbols::Characters);
+
+        assert!(result.is_valid());
+    }
+
+    #[test]
+    fn test_validate() {
+        let input = indoc! {"
+        NNX
+
+        NN -> C
+        NN -> C
+        NC -> B
+        NC -> H
+        CN -> Q
+        ZZ -> Y
+        garbage
+        N -> C"};
+
+        let result = validate(input, Symbols::Characters);
+
+        let pair = |pair: &str| {
+            let (left, right) = Symbols::Characters
+                .parse_elements(pair)
+                .into_iter()
+                .collect_tuple()
+                .unwrap();
+            (left, right)
+        };
+        assert_eq!(
+            Validation {
+                unparseable: vec![(9, "garbage".to_string()), (10, "N -> C".to_string())],
+                duplicates: vec![pair("NN")],
+                conflicts: vec![(pair("NC"), vec!["B".to_string(), "H".to_string()])],
+                missing_rules: ["CQ", "HC", "NH", "NX", "QN"]
+                    .into_iter()
+                    .map(pair)
+                    .collect(),
+                unreachable: vec!["B".to_string(), "Y".to_string(), "Z".to_string()],
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_pairs_without_rules_keep_counts() {
+        let input = indoc! {"
+        NNX
+
+        NN -> X"};
+        let poly = Polymer::<u128>::new(input, Symbols::Characters)
+            .iterate()
+            .unwrap();
+
+        let result = poly.pair_counts();
+
+        let pair = |left: &str, right: &str| (left.to_string(), right.to_string());
+        assert_eq!(vec![(pair("N", "X"), 2), (pair("X", "N"), 1)], result);
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let input = indoc! {"
+        NNX
+
+        NN -> X"};
+
+        let result = Polymer::<u128>::new(input, Symbols::Characters).find_cycle(10);
+
+        assert_eq!(
+            Ok(Some(Cycle {
+                start: 1,
+                period: 1
+            })),
+            result
+        );
+        assert_eq!(
+            Ok(None),
+            Polymer::<u128>::new(EXAMPLE, Symbols::Characters).find_cycle(10)
+        );
+    }
+
+    #[test]
+    fn test_dense_matches_iterate() {
+        let dense = DensePolymer::new(EXAMPLE, Symbols::Characters);
+        let mut poly = Polymer::<u128>::new(EXAMPLE, Symbols::Characters);
+        for steps in 0..=40 {
+            let expected = poly
+                .histogram()
+                .into_iter()
+                .map(|(element, count)| (element, BigUint::from(count)))
+                .sorted()
+                .collect_vec();
+
+            let result = dense.histogram_after(steps, None);
+
+            assert_eq!(expected, result);
+            poly = poly.iterate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_dense_without_rules() {
+        let input = indoc! {"
+        NNX
+
+        NN -> X"};
+        let dense = DensePolymer::new(input, Symbols::Characters);
+
+        let result = dense.histogram_after(5, None);
+
+        let count = |element: &str, count: u32| (element.to_string(), BigUint::from(count));
+        assert_eq!(vec![count("N", 2), count("X", 2)], result);
+    }
+
+    #[test]
+    fn test_dense_modulo_only_present_elements() {
+        let dense = DensePolymer::new(EXAMPLE, Symbols::Characters);
+        let modulus = BigUint::from(7u32);
+
+        let result = dense
+            .histogram_after(0, Some(&modulus))
+            .into_iter()
+            .map(|(element, _)| element)
+            .collect_vec();
+
+        assert_eq!(vec!["B", "C", "N"], result);
+    }
+
+    #[test]
+    fn test_dense_modulo_keeps_zero_residues() {
+        // NN -> N doubles the Ns each step, so there are 2^3 + 1 = 9 of them after 3.
+        let input = indoc! {"
+        NNX
+
+        NN -> N"};
+        let dense = DensePolymer::new(input, Symbols::Characters);
+        let modulus = BigUint::from(3u32);
+
+        let result = dense.histogram_after(3, Some(&modulus));
+
+        let count = |element: &str, count: u32| (element.to_string(), BigUint::from(count));
+        assert_eq!(vec![count("N", 0), count("X", 1)], result);
+    }
+
+    #[test]
+    fn test_dense_million_steps() {
+        let dense = DensePolymer::new(EXAMPLE, Symbols::Characters);
+        let modulus = BigUint::from(1_000_000_007u32);
+
+        let result = dense.histogram_after(1_000_000, Some(&modulus));
 
-        let result = do_the_thing(input, 40);
-        assert_eq!(2188189693529, result);
+        let count = |element: &str, count: u32| (element.to_string(), BigUint::from(count));
+        assert_eq!(
+            vec![
+                count("B", 622022412),
+                count("C", 317023047),
+                count("H", 60926660),
+                count("N", 705154066),
+            ],
+            result
+        );
     }
 }
@@ -1,6 +1,6 @@
+use advent_of_code::day_21::{analyse_quantum, parse_players, DiracRules, Player};
 use itertools::Itertools;
-use regex::Regex;
-use std::fs;
+use std::{cmp::Reverse, fs, io, num::ParseIntError};
 
 fn main() {
     let filename = "day21_input.txt";
@@ -8,24 +8,116 @@ fn main() {
 
     println!("{}", contents);
 
-    let result = do_the_thing(&contents);
+    // Pass --log csv or --log json to print every turn.
+    let log = match std::env::args()
+        .skip_while(|arg| arg != "--log")
+        .nth(1)
+        .as_deref()
+    {
+        Some("csv") => Some(LogFormat::Csv),
+        Some("json") => Some(LogFormat::Json),
+        _ => None,
+    };
+
+    let result = do_the_thing(&contents, &DiracRules::deterministic(), log);
 
     println!("Result {}", result);
+
+    // Pass --replay <file> to play with rolls from a file instead.
+    if let Some(path) = std::env::args().skip_while(|arg| arg != "--replay").nth(1) {
+        let rules = DiracRules::deterministic();
+        let mut dice = ReplayDice::from_file(&path, rules.die_faces).expect("Replay read error");
+        let outcome = play(parse_players(&contents), &rules, &mut dice)
+            .unwrap_or_else(|error| panic!("Replay failed: {}", error));
+        println!(
+            "Replayed {:?} after {} rolls",
+            outcome.ranking, outcome.rolls
+        );
+    }
+
+    // Pass --players-after <log.csv> <turn> to rebuild the players from an exported log.
+    let mut args = std::env::args()
+        .skip_while(|arg| arg != "--players-after")
+        .skip(1);
+    if let (Some(path), Some(turn)) = (args.next(), args.next()) {
+        let log = GameLog::from_csv(&fs::read_to_string(path).expect("Log read error"))
+            .unwrap_or_else(|error| panic!("Invalid log: {}", error));
+        let players = log
+            .players_after(
+                parse_players(&contents),
+                &DiracRules::deterministic(),
+                turn.parse().expect("Invalid turn"),
+            )
+            .unwrap_or_else(|error| panic!("Log doesn't match the players: {}", error));
+        println!("Players after turn {}: {:?}", turn, players);
+    }
+
+    // Pass --monte-carlo <games> to play the quantum rules with a random die, to compare against
+    // the exact odds.
+    if let Some(games) = std::env::args()
+        .skip_while(|arg| arg != "--monte-carlo")
+        .nth(1)
+    {
+        let games = games.parse().expect("Invalid number of games");
+        let players = parse_players(&contents);
+        let rules = DiracRules::quantum();
+        let mut dice = RandomDice::new(rules.die_faces, 2021);
+        println!(
+            "Monte Carlo win rates {:?}, exact {:?}",
+            monte_carlo(&players, &rules, games, &mut dice).expect("Random dice always roll"),
+            analyse_quantum(&players, &rules).win_probability
+        );
+        let mut dice = LoadedDice::new(vec![1, 1, 2], rules.die_faces, 2021);
+        println!(
+            "Monte Carlo win rates with a die loaded towards 3 {:?}",
+            monte_carlo(&players, &rules, games, &mut dice).expect("Loaded dice always roll")
+        );
+    }
 }
 
 trait Dice {
-    fn roll(&mut self) -> u8;
+    fn roll(&mut self) -> Result<u8, DiceError>;
     fn total_rolls(&self) -> u128;
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum DiceError {
+    InvalidRoll(ParseIntError),
+    /// A recorded roll that the die can't show.
+    RollOutOfRange {
+        roll: u8,
+        faces: u8,
+    },
+    /// A replay ended before the game did.
+    OutOfRolls {
+        rolls: u128,
+    },
+}
+
+impl std::fmt::Display for DiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidRoll(error) => write!(f, "invalid roll: {}", error),
+            Self::RollOutOfRange { roll, faces } => {
+                write!(f, "roll {} is not on a die with {} faces", roll, faces)
+            }
+            Self::OutOfRolls { rolls } => write!(f, "ran out of rolls after {}", rolls),
+        }
+    }
+}
+
+impl std::error::Error for DiceError {}
+
 struct DeterministicDice {
+    faces: u8,
     next_roll: u8,
     rolls: u128,
 }
 
 impl DeterministicDice {
-    fn new() -> Self {
+    fn new(faces: u8) -> Self {
         Self {
+            faces,
             next_roll: 1,
             rolls: 0,
         }
@@ -33,19 +125,19 @@ impl DeterministicDice {
 }
 
 impl Dice for DeterministicDice {
-    fn roll(&mut self) -> u8 {
+    fn roll(&mut self) -> Result<u8, DiceError> {
         let next = self.next_roll;
 
-        self.next_roll += 1; // Increment the roll
-
-        if self.next_roll > 100 {
-            // Rollover after 100.
+        if self.next_roll == self.faces {
+            // Rollover after the last face.
             self.next_roll = 1;
+        } else {
+            self.next_roll += 1; // Increment the roll
         }
 
         self.rolls += 1; // Track the roll
 
-        dbg!(next)
+        Ok(next)
     }
 
     fn total_rolls(&self) -> u128 {
@@ -53,95 +145,628 @@ impl Dice for DeterministicDice {
     }
 }
 
-#[derive(Debug)]
-struct Player {
-    position: u8, // value from 1 - 10
-    score: u128,  // Score from moving
+/// A small seeded pseudo-random generator (SplitMix64), so games can be repeated.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A value from 0 up to (not including) `bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        ((self.next() as u128 * bound as u128) >> 64) as u64
+    }
 }
 
-impl Player {
-    fn new(position: u8) -> Self {
-        Self { position, score: 0 }
+/// A fair die rolled by a seeded pseudo-random generator.
+struct RandomDice {
+    faces: u8,
+    rng: SplitMix64,
+    rolls: u128,
+}
+
+impl RandomDice {
+    fn new(faces: u8, seed: u64) -> Self {
+        Self {
+            faces,
+            rng: SplitMix64(seed),
+            rolls: 0,
+        }
     }
+}
 
-    fn wins(&self) -> bool {
-        self.score >= 1000
+impl Dice for RandomDice {
+    fn roll(&mut self) -> Result<u8, DiceError> {
+        self.rolls += 1;
+        Ok(self.rng.below(self.faces as u64) as u8 + 1)
     }
 
-    fn move_player(&mut self, roll: u128) {
-        let change_pos = (roll % 10) as u8; // this tracks how much we actually move, every 10 is a non-move.
+    fn total_rolls(&self) -> u128 {
+        self.rolls
+    }
+}
 
-        self.position += change_pos; // Move around the board
+/// A die where each face comes up in proportion to its weight.
+struct LoadedDice {
+    weights: Vec<u64>, // weights[0] is the weight of rolling 1.
+    rng: SplitMix64,
+    rolls: u128,
+}
 
-        self.position %= 10; // Adjust for passing 10.
+impl LoadedDice {
+    fn new(weights: Vec<u64>, faces: u8, seed: u64) -> Self {
+        assert_eq!(
+            weights.len(),
+            faces as usize,
+            "Every face needs exactly one weight"
+        );
+        assert!(
+            weights.iter().sum::<u64>() > 0,
+            "Some face must have weight"
+        );
+        Self {
+            weights,
+            rng: SplitMix64(seed),
+            rolls: 0,
+        }
+    }
+}
 
-        if self.position == 0 {
-            self.position = 10;
-        } // Adjust for 0 being 10.
+impl Dice for LoadedDice {
+    fn roll(&mut self) -> Result<u8, DiceError> {
+        self.rolls += 1;
 
-        dbg!(self.position);
+        let mut pick = self.rng.below(self.weights.iter().sum());
+        for (face, &weight) in self.weights.iter().enumerate() {
+            if pick < weight {
+                return Ok(face as u8 + 1);
+            }
+            pick -= weight;
+        }
+        unreachable!()
+    }
 
-        self.score += self.position as u128; // Boost score by position we landed at.
+    fn total_rolls(&self) -> u128 {
+        self.rolls
     }
 }
 
-fn do_the_thing(input: &str) -> u128 {
-    let re = Regex::new(r#"Player (\d) starting position: (\d+)"#).unwrap();
+/// Replays a recorded sequence of rolls.
+struct ReplayDice {
+    recorded: Vec<u8>,
+    rolls: u128,
+}
 
-    let mut players = input
-        .lines()
-        .map(|s| {
-            let captures = re.captures(s).unwrap();
-            Player::new(captures.get(2).unwrap().as_str().parse::<u8>().unwrap())
-        })
-        .collect_vec();
+impl ReplayDice {
+    /// Rolls of a die with `faces` faces, separated by whitespace or commas.
+    fn parse(input: &str, faces: u8) -> Result<Self, DiceError> {
+        let recorded = input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|roll| !roll.is_empty())
+            .map(|roll| {
+                let roll = roll.parse().map_err(DiceError::InvalidRoll)?;
+                if (1..=faces).contains(&roll) {
+                    Ok(roll)
+                } else {
+                    Err(DiceError::RollOutOfRange { roll, faces })
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { recorded, rolls: 0 })
+    }
 
-    let mut dice = DeterministicDice::new();
-    'game: loop {
-        '_turn: for player in players.iter_mut() {
-            let roll = (0..3).map(|_| dice.roll() as u128).sum();
-            player.move_player(roll);
+    fn from_file(path: &str, faces: u8) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?, faces)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+impl Dice for ReplayDice {
+    fn roll(&mut self) -> Result<u8, DiceError> {
+        let roll = *self
+            .recorded
+            .get(self.rolls as usize)
+            .ok_or(DiceError::OutOfRolls { rolls: self.rolls })?;
+        self.rolls += 1;
+        Ok(roll)
+    }
+
+    fn total_rolls(&self) -> u128 {
+        self.rolls
+    }
+}
+
+/// One turn of a game, as recorded in a GameLog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Turn {
+    player: usize, // Index of the player who moved.
+    rolls: Vec<u8>,
+    space: u8,   // Where the player landed.
+    score: u128, // The player's score after landing.
+    total_rolls: u128,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct GameLog {
+    turns: Vec<Turn>,
+}
+
+impl GameLog {
+    /// One line per turn, with the rolls separated by spaces.
+    fn to_csv(&self) -> String {
+        std::iter::once("player,rolls,space,score,total_rolls".to_string())
+            .chain(self.turns.iter().map(|turn| {
+                format!(
+                    "{},{},{},{},{}",
+                    turn.player,
+                    turn.rolls.iter().join(" "),
+                    turn.space,
+                    turn.score,
+                    turn.total_rolls
+                )
+            }))
+            .join("\n")
+    }
+
+    fn from_csv(input: &str) -> Result<Self, LogError> {
+        let turns = input
+            .lines()
+            .enumerate()
+            .skip(1) // The header.
+            .map(|(index, line)| {
+                let line_number = index + 1;
+                let number = |error| LogError::InvalidNumber { line_number, error };
+                let (player, rolls, space, score, total_rolls) = line
+                    .split(',')
+                    .collect_tuple()
+                    .ok_or(LogError::FieldCount { line_number })?;
+                Ok(Turn {
+                    player: player.parse().map_err(number)?,
+                    rolls: rolls
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(number)?,
+                    space: space.parse().map_err(number)?,
+                    score: score.parse().map_err(number)?,
+                    total_rolls: total_rolls.parse().map_err(number)?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { turns })
+    }
+
+    fn to_json(&self) -> String {
+        let turns = self
+            .turns
+            .iter()
+            .map(|turn| {
+                format!(
+                    r#"{{"player":{},"rolls":[{}],"space":{},"score":{},"total_rolls":{}}}"#,
+                    turn.player,
+                    turn.rolls.iter().join(","),
+                    turn.space,
+                    turn.score,
+                    turn.total_rolls
+                )
+            })
+            .join(",");
+
+        format!("[{}]", turns)
+    }
 
-            if player.wins() {
+    /// Replays the first `turns` turns from the starting players, returning the players after them.
+    fn players_after(
+        &self,
+        mut players: Vec<Player>,
+        rules: &DiracRules,
+        turns: usize,
+    ) -> Result<Vec<Player>, LogError> {
+        let player_count = players.len();
+        for (index, turn) in self.turns.iter().take(turns).enumerate() {
+            let player = players
+                .get_mut(turn.player)
+                .ok_or(LogError::UnknownPlayer {
+                    turn: index + 1,
+                    player: turn.player,
+                    player_count,
+                })?;
+            let roll = turn.rolls.iter().map(|&roll| roll as u128).sum();
+            player.move_player(roll, rules);
+        }
+
+        Ok(players)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum LogError {
+    /// A line didn't have the five fields of a turn.
+    FieldCount { line_number: usize },
+    InvalidNumber {
+        line_number: usize,
+        error: ParseIntError,
+    },
+    /// A turn was taken by a player that isn't in the game being replayed.
+    UnknownPlayer {
+        turn: usize,
+        player: usize,
+        player_count: usize,
+    },
+}
+
+impl std::fmt::Display for LogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FieldCount { line_number } => {
+                write!(f, "line {} doesn't have 5 fields", line_number)
+            }
+            Self::InvalidNumber { line_number, error } => {
+                write!(f, "line {}: {}", line_number, error)
+            }
+            Self::UnknownPlayer {
+                turn,
+                player,
+                player_count,
+            } => write!(
+                f,
+                "turn {} was taken by player {}, but there are only {} players",
+                turn, player, player_count
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    ranking: Vec<(usize, u128)>, // (player index, score), from the winner to the last placed loser.
+    rolls: u128,
+    log: GameLog,
+}
+
+fn play(
+    mut players: Vec<Player>,
+    rules: &DiracRules,
+    dice: &mut impl Dice,
+) -> Result<Outcome, DiceError> {
+    let rolls_before = dice.total_rolls(); // The dice may have been used for earlier games.
+    let mut log = GameLog::default();
+    'game: loop {
+        '_turn: for (index, player) in players.iter_mut().enumerate() {
+            let rolls = (0..rules.rolls_per_turn)
+                .map(|_| dice.roll())
+                .collect::<Result<Vec<_>, _>>()?;
+            player.move_player(rolls.iter().map(|&roll| roll as u128).sum(), rules);
+            log.turns.push(Turn {
+                player: index,
+                rolls,
+                space: player.position,
+                score: player.score,
+                total_rolls: dice.total_rolls() - rolls_before,
+            });
+
+            if player.wins(rules) {
                 break 'game;
             }
             // thread::sleep(time::Duration::from_secs(1));
         }
     }
 
-    let loser = players
+    let ranking = players
         .iter()
-        .inspect(|p| println!("{:?}", p))
         .map(|p| p.score)
-        .min()
-        .unwrap();
-    let rolls = dice.total_rolls();
+        .enumerate()
+        .sorted_by_key(|&(_, score)| Reverse(score))
+        .collect_vec();
 
-    loser * rolls
+    Ok(Outcome {
+        ranking,
+        rolls: dice.total_rolls() - rolls_before,
+        log,
+    })
+}
+
+/// Plays `games` games and returns the fraction each player won.
+fn monte_carlo(
+    players: &[Player],
+    rules: &DiracRules,
+    games: u32,
+    dice: &mut impl Dice,
+) -> Result<Vec<f64>, DiceError> {
+    let mut wins = vec![0; players.len()];
+    for _ in 0..games {
+        let outcome = play(players.to_vec(), rules, dice)?;
+        wins[outcome.ranking[0].0] += 1;
+    }
+
+    Ok(wins
+        .into_iter()
+        .map(|wins| wins as f64 / games as f64)
+        .collect())
+}
+
+/// How to print the turn log of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Csv,
+    Json,
+}
+
+fn do_the_thing(input: &str, rules: &DiracRules, log: Option<LogFormat>) -> u128 {
+    let players = parse_players(input);
+
+    let mut dice = DeterministicDice::new(rules.die_faces);
+    let outcome = play(players, rules, &mut dice).expect("Deterministic dice always roll");
+    println!("{:?} after {} rolls", outcome.ranking, outcome.rolls);
+
+    match log {
+        Some(LogFormat::Csv) => println!("{}", outcome.log.to_csv()),
+        Some(LogFormat::Json) => println!("{}", outcome.log.to_json()),
+        None => (),
+    }
+
+    let (_, loser) = outcome.ranking.last().unwrap();
+
+    loser * outcome.rolls
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use indoc::indoc;
+    use test_case::test_case;
 
     #[test]
     fn test_example() {
         let input = indoc! {"Player 1 starting position: 4
         Player 2 starting position: 8"};
 
-        let result = do_the_thing(input);
+        let result = do_the_thing(input, &DiracRules::deterministic(), None);
 
         assert_eq!(739785, result);
     }
 
+    #[test]
+    fn test_d6_20_spaces_2_rolls_to_50() {
+        let input = indoc! {"Player 1 starting position: 4
+        Player 2 starting position: 8"};
+        let rules = DiracRules {
+            board_size: 20,
+            die_faces: 6,
+            rolls_per_turn: 2,
+            win_score: 50,
+        };
+
+        let result = do_the_thing(input, &rules, None);
+
+        assert_eq!(608, result);
+    }
+
     #[test]
     fn test_start_at_10() {
         let mut player = Player::new(10);
 
         let roll = 1 + 2 + 3;
-        player.move_player(roll);
+        player.move_player(roll, &DiracRules::deterministic());
 
         assert_eq!(player.position, 6);
     }
+
+    #[test]
+    fn test_three_players() {
+        let players = vec![Player::new(4), Player::new(8), Player::new(1)];
+        let rules = DiracRules::deterministic();
+        let mut dice = DeterministicDice::new(rules.die_faces);
+
+        let result = play(players, &rules, &mut dice).unwrap();
+
+        assert_eq!(vec![(0, 1001), (1, 987), (2, 855)], result.ranking);
+        assert_eq!(1380, result.rolls);
+    }
+
+    #[test]
+    fn test_four_players_ranking() {
+        let input = indoc! {"Player 1 starting position: 4
+        Player 2 starting position: 8
+        Player 3 starting position: 1
+        Player 4 starting position: 5"};
+        let rules = DiracRules::deterministic();
+        let mut dice = DeterministicDice::new(rules.die_faces);
+
+        let result = play(parse_players(input), &rules, &mut dice).unwrap();
+
+        assert_eq!(
+            vec![(0, 1000), (3, 910), (1, 895), (2, 495)],
+            result.ranking
+        );
+        assert_eq!(981585, do_the_thing(input, &rules, None));
+    }
+
+    #[test]
+    fn test_random_dice_is_seeded() {
+        let mut first = RandomDice::new(6, 42);
+        let mut second = RandomDice::new(6, 42);
+
+        let first = (0..100).map(|_| first.roll().unwrap()).collect_vec();
+        let second = (0..100).map(|_| second.roll().unwrap()).collect_vec();
+
+        assert_eq!(first, second);
+        assert!(first.iter().all(|roll| (1..=6).contains(roll)));
+        assert_eq!(6, first.iter().unique().count());
+    }
+
+    #[test]
+    fn test_loaded_dice() {
+        let mut dice = LoadedDice::new(vec![0, 1, 3], 3, 7);
+
+        let counts = (0..4000).map(|_| dice.roll().unwrap()).counts();
+
+        assert_eq!(None, counts.get(&1));
+        assert!((900..1100).contains(&counts[&2]), "{:?}", counts);
+        assert!((2900..3100).contains(&counts[&3]), "{:?}", counts);
+        assert_eq!(4000, dice.total_rolls());
+    }
+
+    #[test]
+    fn test_replay_matches_deterministic() {
+        let input = indoc! {"Player 1 starting position: 4
+        Player 2 starting position: 8"};
+        let rules = DiracRules::deterministic();
+        let mut deterministic = DeterministicDice::new(rules.die_faces);
+        let recorded = (0..993).map(|_| deterministic.roll().unwrap()).join(", ");
+        let mut dice = ReplayDice::parse(&recorded, rules.die_faces).unwrap();
+
+        let result = play(parse_players(input), &rules, &mut dice).unwrap();
+
+        assert_eq!(vec![(0, 1000), (1, 745)], result.ranking);
+        assert_eq!(993, result.rolls);
+    }
+
+    #[test]
+    fn test_replay_invalid_roll() {
+        let result = ReplayDice::parse("1, 2, three", 6);
+
+        assert!(matches!(result, Err(DiceError::InvalidRoll(_))));
+    }
+
+    #[test_case("1, 2, 7", 7; "above the faces")]
+    #[test_case("0, 1, 2", 0; "zero")]
+    fn test_replay_roll_out_of_range(input: &str, roll: u8) {
+        let result = ReplayDice::parse(input, 6);
+
+        assert_eq!(
+            Err(DiceError::RollOutOfRange { roll, faces: 6 }),
+            result.map(|dice| dice.recorded)
+        );
+    }
+
+    #[test]
+    fn test_replay_runs_out_of_rolls() {
+        let players = vec![Player::new(4), Player::new(8)];
+        let rules = DiracRules::deterministic();
+        let mut dice = ReplayDice::parse("1 2 3 4 5 6 7", rules.die_faces).unwrap();
+
+        let result = play(players, &rules, &mut dice);
+
+        assert_eq!(Err(DiceError::OutOfRolls { rolls: 7 }), result);
+    }
+
+    #[test]
+    #[should_panic(expected = "Every face needs exactly one weight")]
+    fn test_loaded_dice_weight_per_face() {
+        LoadedDice::new(vec![1, 1, 2], 6, 7);
+    }
+
+    #[test]
+    fn test_monte_carlo_matches_quantum_odds() {
+        let players = [Player::new(4), Player::new(8)];
+        let rules = DiracRules::quantum();
+        let mut dice = RandomDice::new(rules.die_faces, 2021);
+
+        let result = monte_carlo(&players, &rules, 20_000, &mut dice).unwrap();
+
+        let exact = analyse_quantum(&players, &rules).win_probability;
+        for (estimate, exact) in result.iter().zip(exact) {
+            assert!((estimate - exact).abs() < 0.02, "{} vs {}", estimate, exact);
+        }
+    }
+
+    #[test]
+    fn test_log() {
+        let input = indoc! {"Player 1 starting position: 4
+        Player 2 starting position: 8"};
+        let rules = DiracRules::deterministic();
+        let mut dice = DeterministicDice::new(rules.die_faces);
+
+        let log = play(parse_players(input), &rules, &mut dice).unwrap().log;
+
+        assert_eq!(331, log.turns.len());
+        assert_eq!(
+            indoc! {"
+                player,rolls,space,score,total_rolls
+                0,1 2 3,10,10,3
+                1,4 5 6,3,3,6
+                0,7 8 9,4,14,9
+                1,10 11 12,6,9,12"},
+            log.to_csv().lines().take(5).join("\n")
+        );
+        assert!(log.to_json().starts_with(
+            r#"[{"player":0,"rolls":[1,2,3],"space":10,"score":10,"total_rolls":3},{"player":1,"#
+        ));
+        assert_eq!(Ok(&log), GameLog::from_csv(&log.to_csv()).as_ref());
+    }
+
+    #[test]
+    fn test_log_replay() {
+        let input = indoc! {"Player 1 starting position: 4
+        Player 2 starting position: 8"};
+        let rules = DiracRules::deterministic();
+        let mut dice = DeterministicDice::new(rules.die_faces);
+        let log = play(parse_players(input), &rules, &mut dice).unwrap().log;
+
+        let after_4 = log.players_after(parse_players(input), &rules, 4).unwrap();
+        let at_end = log
+            .players_after(parse_players(input), &rules, log.turns.len())
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                Player {
+                    position: 4,
+                    score: 14
+                },
+                Player {
+                    position: 6,
+                    score: 9
+                }
+            ],
+            after_4
+        );
+        assert_eq!(
+            vec![1000, 745],
+            at_end.iter().map(|p| p.score).collect_vec()
+        );
+    }
+
+    #[test_case("0,1 2 3,10,10", LogError::FieldCount { line_number: 2 }; "missing field")]
+    #[test_case("0,1 two 3,10,10,3", LogError::InvalidNumber {
+        line_number: 2,
+        error: "two".parse::<u8>().unwrap_err()
+    }; "invalid roll")]
+    fn test_log_invalid_line(line: &str, expected: LogError) {
+        let input = format!("player,rolls,space,score,total_rolls\n{}", line);
+
+        let result = GameLog::from_csv(&input);
+
+        assert_eq!(Err(expected), result);
+    }
+
+    #[test]
+    fn test_log_replay_unknown_player() {
+        let input = indoc! {"
+            player,rolls,space,score,total_rolls
+            0,1 2 3,10,10,3
+            1,4 5 6,3,3,6
+            2,7 8 9,4,14,9"};
+        let log = GameLog::from_csv(input).unwrap();
+        let players = vec![Player::new(4), Player::new(8)];
+
+        let result = log.players_after(players, &DiracRules::deterministic(), 3);
+
+        assert_eq!(
+            Err(LogError::UnknownPlayer {
+                turn: 3,
+                player: 2,
+                player_count: 2
+            }),
+            result
+        );
+    }
 }
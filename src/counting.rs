@@ -0,0 +1,117 @@
+use num_bigint::BigUint;
+use num_traits::{CheckedAdd, CheckedMul, CheckedSub, One, Zero};
+
+/// A number that grows quickly, e.g. lanternfish, polymer pairs or packet values.
+///
+/// Fixed width counts report overflow instead of wrapping, and `BigUint` never overflows.
+pub trait Count:
+    Zero + One + CheckedAdd + CheckedSub + CheckedMul + Ord + Clone + std::fmt::Debug
+{
+    /// Converts from arbitrary precision, returning None if it doesn't fit.
+    fn from_biguint(value: &BigUint) -> Option<Self>;
+
+    fn to_biguint(&self) -> BigUint;
+}
+
+impl Count for u64 {
+    fn from_biguint(value: &BigUint) -> Option<Self> {
+        u64::try_from(value).ok()
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        BigUint::from(*self)
+    }
+}
+
+impl Count for u128 {
+    fn from_biguint(value: &BigUint) -> Option<Self> {
+        u128::try_from(value).ok()
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        BigUint::from(*self)
+    }
+}
+
+impl Count for BigUint {
+    fn from_biguint(value: &BigUint) -> Option<Self> {
+        Some(value.clone())
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountError {
+    /// The count didn't fit in the chosen number type.
+    Overflow,
+    /// More was taken away from a count than it held.
+    Underflow,
+}
+
+impl std::fmt::Display for CountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overflow => write!(f, "count overflowed"),
+            Self::Underflow => write!(f, "count went below zero"),
+        }
+    }
+}
+
+pub fn add<T: Count>(a: &T, b: &T) -> Result<T, CountError> {
+    a.checked_add(b).ok_or(CountError::Overflow)
+}
+
+pub fn sub<T: Count>(a: &T, b: &T) -> Result<T, CountError> {
+    a.checked_sub(b).ok_or(CountError::Underflow)
+}
+
+pub fn sum<'a, T: Count + 'a>(counts: impl IntoIterator<Item = &'a T>) -> Result<T, CountError> {
+    counts
+        .into_iter()
+        .try_fold(T::zero(), |total, count| add(&total, count))
+}
+
+/// Runs `count` with u128, falling back to `BigUint` if that overflows.
+pub fn with_fallback<F, G>(count: F, big_count: G) -> Result<BigUint, CountError>
+where
+    F: FnOnce() -> Result<u128, CountError>,
+    G: FnOnce() -> Result<BigUint, CountError>,
+{
+    match count() {
+        Ok(count) => Ok(BigUint::from(count)),
+        Err(CountError::Overflow) => big_count(),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow() {
+        let result = add(&u64::MAX, &1);
+
+        assert_eq!(Err(CountError::Overflow), result);
+    }
+
+    #[test]
+    fn test_underflow() {
+        let result = sub(&BigUint::from(1u32), &BigUint::from(2u32));
+
+        assert_eq!(Err(CountError::Underflow), result);
+    }
+
+    #[test]
+    fn test_fallback() {
+        let result = with_fallback(
+            || sum(&[u128::MAX, 1]),
+            || sum(&[BigUint::from(u128::MAX), BigUint::one()]),
+        );
+
+        assert_eq!(Ok(BigUint::from(u128::MAX) + 1u32), result);
+    }
+}
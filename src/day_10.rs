@@ -0,0 +1,621 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, BufRead},
+};
+
+/// Which characters open and close chunks, which can be skipped over, and what closers score.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketLanguage {
+    pairs: Vec<(char, char)>, // (open, close)
+    ignored: HashSet<char>,
+    points: HashMap<char, (u128, u128)>, // close -> (syntax error points, autocomplete points)
+}
+
+impl BracketLanguage {
+    pub fn new(pairs: &[(char, char)]) -> Self {
+        Self {
+            pairs: pairs.to_vec(),
+            ignored: HashSet::new(),
+            points: HashMap::new(),
+        }
+    }
+
+    /// The four kinds of chunk in the navigation subsystem, scored as in the puzzle.
+    pub fn standard() -> Self {
+        Self::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')]).scoring(&[
+            (')', 3, 1),
+            (']', 57, 2),
+            ('}', 1197, 3),
+            ('>', 25137, 4),
+        ])
+    }
+
+    /// Also allows `ignored` anywhere, without them affecting the chunks.
+    pub fn ignoring(mut self, ignored: &str) -> Self {
+        assert!(
+            !self
+                .pairs
+                .iter()
+                .any(|&(open, close)| ignored.contains(open) || ignored.contains(close)),
+            "Ignored characters can't also open or close chunks!"
+        );
+        self.ignored.extend(ignored.chars());
+        self
+    }
+
+    /// Sets the points for closers, given as `(close, syntax error points, autocomplete points)`.
+    pub fn scoring(mut self, points: &[(char, u128, u128)]) -> Self {
+        for &(close, syntax_error, autocomplete) in points {
+            assert!(self.is_closer(close), "Only closers can be scored!");
+            self.points.insert(close, (syntax_error, autocomplete));
+        }
+        self
+    }
+
+    fn closer(&self, open: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|&&(o, _)| o == open)
+            .map(|&(_, close)| close)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|&(_, close)| close == c)
+    }
+
+    /// Reads a line once, stopping at the first character that doesn't belong.
+    pub fn classify(&self, line: &str) -> LineReport {
+        self.check(line, Recovery::Stop)
+    }
+
+    /// Checks a line, carrying on past errors according to `recovery`.
+    pub fn check(&self, line: &str, recovery: Recovery) -> LineReport {
+        let mut stack = Vec::new(); // The closers we're waiting on.
+        let mut errors = Vec::new();
+        for (position, c) in line.chars().enumerate() {
+            if self.ignored.contains(&c) {
+                continue;
+            }
+            if stack.last() == Some(&c) {
+                stack.pop();
+                continue;
+            }
+            if let Some(close) = self.closer(c) {
+                stack.push(close);
+                continue;
+            }
+
+            errors.push(SyntaxError {
+                position,
+                expected: stack.last().copied(),
+                found: c,
+            });
+            match recovery {
+                Recovery::Stop => break,
+                Recovery::Skip => (),
+                Recovery::CloseToMatch => {
+                    // Assume the closers up to the matching chunk were left out.
+                    if self.is_closer(c) {
+                        if let Some(depth) = stack.iter().rposition(|&close| close == c) {
+                            stack.truncate(depth);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            LineReport::Corrupted { errors }
+        } else if stack.is_empty() {
+            LineReport::Valid
+        } else {
+            LineReport::Incomplete {
+                completion: stack.into_iter().rev().collect(),
+            }
+        }
+    }
+
+    /// Fixes a corrupted line with as few edits as possible, returning None if it isn't corrupted.
+    ///
+    /// Each edit either deletes the first character that doesn't belong, or replaces it with the
+    /// closer that was expected, and the lines they lead to are searched breadth first. When
+    /// several repairs are equally short, replacing is tried before deleting.
+    pub fn repair(&self, line: &str) -> Option<Repair> {
+        let first_error = |line: &str| self.classify(line).first_error().copied();
+
+        let mut queue = VecDeque::from([(
+            Repair {
+                edits: Vec::new(),
+                line: line.to_string(),
+            },
+            first_error(line)?,
+        )]);
+        let mut seen = HashSet::from([line.to_string()]);
+        while let Some((repair, error)) = queue.pop_front() {
+            let replace = error.expected.map(|with| Edit::Replace {
+                position: error.position,
+                with,
+            });
+            let delete = Edit::Delete {
+                position: error.position,
+            };
+            for edit in replace.into_iter().chain([delete]) {
+                let line = edit.apply(&repair.line);
+                if !seen.insert(line.clone()) {
+                    continue;
+                }
+                let mut edits = repair.edits.clone();
+                edits.push(edit);
+                let next = Repair { edits, line };
+                match first_error(&next.line) {
+                    Some(next_error) => queue.push_back((next, next_error)),
+                    None => return Some(next),
+                }
+            }
+        }
+
+        // Deleting every character that doesn't belong always fixes a line.
+        unreachable!()
+    }
+
+    /// Part 1's score, for the first character that doesn't belong.
+    ///
+    /// Lines that aren't corrupted score nothing. Returns None if that character has no points.
+    pub fn syntax_error_score(&self, report: &LineReport) -> Option<u128> {
+        match report.first_error() {
+            Some(error) => self
+                .points
+                .get(&error.found)
+                .map(|&(syntax_error, _)| syntax_error),
+            None => Some(0),
+        }
+    }
+
+    /// Part 2's score for the completion, working from the left to the right.
+    ///
+    /// Corrupted lines can't be completed, so they don't have one. Returns None as well if a
+    /// closer in the completion has no points.
+    pub fn autocomplete_score(&self, report: &LineReport) -> Option<u128> {
+        report.completion()?.chars().try_fold(0, |acc, c| {
+            let &(_, autocomplete) = self.points.get(&c)?;
+            Some(acc * 5 + autocomplete)
+        })
+    }
+}
+
+/// One change to a line, at a position in the line as it is when the change is made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Replace { position: usize, with: char },
+    Delete { position: usize },
+}
+
+impl Edit {
+    fn apply(&self, line: &str) -> String {
+        let mut chars = line.chars().collect::<Vec<_>>();
+        match *self {
+            Edit::Replace { position, with } => chars[position] = with,
+            Edit::Delete { position } => {
+                chars.remove(position);
+            }
+        }
+        chars.into_iter().collect()
+    }
+}
+
+/// The edits that stop a line being corrupted, and the line after them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    pub edits: Vec<Edit>,
+    pub line: String,
+}
+
+/// What to do after finding a character that doesn't belong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Give up on the rest of the line.
+    Stop,
+    /// Pretend the character wasn't there.
+    Skip,
+    /// If the character closes a chunk further out, close everything up to it, otherwise skip it.
+    CloseToMatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub position: usize,        // Index of the character in the line.
+    pub expected: Option<char>, // The closer for the innermost open chunk, if there is one.
+    pub found: char,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineReport {
+    Valid,
+    /// The line has characters that don't belong, in the order they were found.
+    Corrupted {
+        errors: Vec<SyntaxError>,
+    },
+    /// The line is fine so far, but needs `completion` adding to close every chunk.
+    Incomplete {
+        completion: String,
+    },
+}
+
+impl LineReport {
+    /// The first character that doesn't belong, which is all that stopping at it finds.
+    pub fn first_error(&self) -> Option<&SyntaxError> {
+        match self {
+            LineReport::Corrupted { errors } => errors.first(),
+            _ => None,
+        }
+    }
+
+    /// The closers that finish an incomplete line, or nothing for a valid one.
+    pub fn completion(&self) -> Option<&str> {
+        match self {
+            LineReport::Valid => Some(""),
+            LineReport::Corrupted { .. } => None,
+            LineReport::Incomplete { completion } => Some(completion),
+        }
+    }
+}
+
+/// Classifies each line from `reader` as it's read, so the whole input never needs to be in memory.
+pub fn classify_reader<'a, R: BufRead + 'a>(
+    language: &'a BracketLanguage,
+    reader: R,
+) -> impl Iterator<Item = io::Result<LineReport>> + 'a {
+    reader
+        .lines()
+        .map(move |line| line.map(|line| language.classify(&line)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("([])" ; "pair in pair")]
+    #[test_case("{()()()}" ; "siblings")]
+    #[test_case("<([{}])>" ; "every kind")]
+    #[test_case("[<>({}){}[([])<>]]" ; "mixed")]
+    #[test_case("(((((((((())))))))))" ; "deep")]
+    fn test_valid(line: &str) {
+        let result = BracketLanguage::standard().check(line, Recovery::Stop);
+
+        assert_eq!(LineReport::Valid, result);
+    }
+
+    #[test_case("{([(<{}[<>[]}>{[]{[(<()>", 12, ']', '}')]
+    #[test_case("[[<[([]))<([[{}[[()]]]", 8, ']', ')')]
+    #[test_case("[{[{({}]{}}([{[{{{}}([]", 7, ')', ']')]
+    #[test_case("[<(<(<(<{}))><([]([]()", 10, '>', ')')]
+    #[test_case("<{([([[(<>()){}]>(<<{{", 16, ']', '>')]
+    fn test_corrupted(line: &str, position: usize, expected: char, found: char) {
+        let result = BracketLanguage::standard().check(line, Recovery::Stop);
+
+        assert_eq!(
+            LineReport::Corrupted {
+                errors: vec![SyntaxError {
+                    position,
+                    expected: Some(expected),
+                    found
+                }]
+            },
+            result
+        );
+    }
+
+    #[test_case("[({(<(())[]>[[{[]{<()<>>", "}}]])})]" ; "example 1")]
+    #[test_case("[(()[<>])]({[<{<<[]>>(", ")}>]})" ; "example 2")]
+    #[test_case("(((({<>}<{<{<>}{[]{[]{}", "}}>}>))))" ; "example 3")]
+    #[test_case("{<[[]]>}<{[{[{[]{()[[[]", "]]}}]}]}>" ; "example 4")]
+    #[test_case("<{([{{}}[<[[[<>{}]]]>[]]", "])}>" ; "example 5")]
+    fn test_incomplete(line: &str, expected: &str) {
+        let result = BracketLanguage::standard().check(line, Recovery::Stop);
+
+        assert_eq!(
+            LineReport::Incomplete {
+                completion: expected.to_string()
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_custom_language() {
+        let language = BracketLanguage::new(&[('/', '\\'), ('«', '»')]).ignoring(" x");
+
+        assert_eq!(
+            LineReport::Valid,
+            language.check("/ «x» \\", Recovery::Stop)
+        );
+        assert_eq!(
+            LineReport::Incomplete {
+                completion: "»\\".to_string()
+            },
+            language.check("/«", Recovery::Stop)
+        );
+        assert_eq!(
+            LineReport::Corrupted {
+                errors: vec![SyntaxError {
+                    position: 1,
+                    expected: Some('\\'),
+                    found: '('
+                }]
+            },
+            language.check("/()\\", Recovery::Stop)
+        );
+    }
+
+    #[test]
+    fn test_same_open_and_close() {
+        let language = BracketLanguage::new(&[('|', '|'), ('(', ')')]);
+
+        assert_eq!(LineReport::Valid, language.check("|(||)|", Recovery::Stop));
+        assert_eq!(
+            LineReport::Incomplete {
+                completion: "|)|".to_string()
+            },
+            language.check("|(|", Recovery::Stop)
+        );
+        assert_eq!(LineReport::Valid, language.classify("||()"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Ignored characters can't also open or close chunks!")]
+    fn test_ignored_delimiter() {
+        BracketLanguage::standard().ignoring(" )");
+    }
+
+    #[test]
+    fn test_leading_closer() {
+        let result = BracketLanguage::standard().check(")", Recovery::Stop);
+
+        assert_eq!(
+            LineReport::Corrupted {
+                errors: vec![SyntaxError {
+                    position: 0,
+                    expected: None,
+                    found: ')'
+                }]
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_unknown_character() {
+        let result = BracketLanguage::standard().check("(a)", Recovery::Skip);
+
+        assert_eq!(
+            LineReport::Corrupted {
+                errors: vec![SyntaxError {
+                    position: 1,
+                    expected: Some(')'),
+                    found: 'a'
+                }]
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_skip_recovery() {
+        let result = BracketLanguage::standard().check("(]>)[", Recovery::Skip);
+
+        assert_eq!(
+            LineReport::Corrupted {
+                errors: vec![
+                    SyntaxError {
+                        position: 1,
+                        expected: Some(')'),
+                        found: ']'
+                    },
+                    SyntaxError {
+                        position: 2,
+                        expected: Some(')'),
+                        found: '>'
+                    },
+                ]
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_first_error() {
+        let language = BracketLanguage::standard();
+
+        let result = language.check("(]>)[", Recovery::Skip);
+
+        assert_eq!(
+            Some(&SyntaxError {
+                position: 1,
+                expected: Some(')'),
+                found: ']'
+            }),
+            result.first_error()
+        );
+        assert_eq!(None, language.check("[(", Recovery::Skip).first_error());
+    }
+
+    #[test]
+    fn test_close_to_match_recovery() {
+        // The ] closes the missing ) as well as the [, so the {} after it are fine.
+        let result = BracketLanguage::standard().check("[(]{}>", Recovery::CloseToMatch);
+
+        assert_eq!(
+            LineReport::Corrupted {
+                errors: vec![
+                    SyntaxError {
+                        position: 2,
+                        expected: Some(')'),
+                        found: ']'
+                    },
+                    SyntaxError {
+                        position: 5,
+                        expected: None,
+                        found: '>'
+                    },
+                ]
+            },
+            result
+        );
+    }
+
+    #[test_case("{([(<{}[<>[]}>{[]{[(<()>", 1197)]
+    #[test_case("[[<[([]))<([[{}[[()]]]", 3 ; "parenthesis 1")]
+    #[test_case("[{[{({}]{}}([{[{{{}}([]", 57)]
+    #[test_case("[<(<(<(<{}))><([]([]()", 3 ; "parenthesis 2")]
+    #[test_case("<{([([[(<>()){}]>(<<{{", 25137)]
+    #[test_case("[({(<(())[]>[[{[]{<()<>>", 0)]
+    fn test_syntax_error_score(line: &str, expected: u128) {
+        let language = BracketLanguage::standard();
+        let report = language.check(line, Recovery::Stop);
+
+        let result = language.syntax_error_score(&report);
+
+        assert_eq!(Some(expected), result);
+    }
+
+    #[test_case("[({(<(())[]>[[{[]{<()<>>", Some(288957))]
+    #[test_case("<{([{{}}[<[[[<>{}]]]>[]]", Some(294))]
+    #[test_case("{([(<{}[<>[]}>{[]{[(<()>", None)]
+    #[test_case("<>", Some(0))]
+    fn test_autocomplete_score(line: &str, expected: Option<u128>) {
+        let language = BracketLanguage::standard();
+        let report = language.check(line, Recovery::Stop);
+
+        let result = language.autocomplete_score(&report);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_custom_scoring() {
+        let language = BracketLanguage::new(&[('/', '\\'), ('«', '»')]).scoring(&[('»', 7, 2)]);
+
+        assert_eq!(
+            Some(7),
+            language.syntax_error_score(&language.classify("/»"))
+        );
+        assert_eq!(
+            Some(2),
+            language.autocomplete_score(&language.classify("«"))
+        );
+        // The \ isn't scored.
+        assert_eq!(None, language.syntax_error_score(&language.classify("«\\")));
+        assert_eq!(None, language.autocomplete_score(&language.classify("/«")));
+    }
+
+    #[test]
+    fn test_unscored_character() {
+        let language = BracketLanguage::standard();
+
+        let result = language.syntax_error_score(&language.classify("(a)"));
+
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only closers can be scored!")]
+    fn test_scoring_opener() {
+        BracketLanguage::standard().scoring(&[('(', 1, 1)]);
+    }
+
+    #[test]
+    fn test_repair_replace() {
+        let result = BracketLanguage::standard().repair("{([(<{}[<>[]}>{[]{[(<()>");
+
+        assert_eq!(
+            Some(Repair {
+                edits: vec![Edit::Replace {
+                    position: 12,
+                    with: ']'
+                }],
+                line: "{([(<{}[<>[]]>{[]{[(<()>".to_string()
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_repair_delete() {
+        // The ) is stray, so replacing it would only move the problem along.
+        let result = BracketLanguage::standard().repair("[<)>]");
+
+        assert_eq!(
+            Some(Repair {
+                edits: vec![Edit::Delete { position: 2 }],
+                line: "[<>]".to_string()
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_repair_several() {
+        let result = BracketLanguage::standard().repair(")(]").unwrap();
+
+        assert_eq!(
+            vec![
+                Edit::Delete { position: 0 },
+                Edit::Replace {
+                    position: 1,
+                    with: ')'
+                }
+            ],
+            result.edits
+        );
+        assert_eq!("()", result.line);
+    }
+
+    #[test]
+    fn test_repair_fewest_edits() {
+        // Replacing the ] first would leave both )s stray.
+        let result = BracketLanguage::standard().repair("(]))");
+
+        assert_eq!(
+            Some(Repair {
+                edits: vec![Edit::Delete { position: 1 }, Edit::Delete { position: 2 }],
+                line: "()".to_string()
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_repair_not_corrupted() {
+        let result = BracketLanguage::standard().repair("[(");
+
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    fn test_classify_reader() {
+        let language = BracketLanguage::standard();
+        let input = "<>\n[(\n)\n";
+
+        let result = classify_reader(&language, input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                LineReport::Valid,
+                LineReport::Incomplete {
+                    completion: ")]".to_string()
+                },
+                LineReport::Corrupted {
+                    errors: vec![SyntaxError {
+                        position: 0,
+                        expected: None,
+                        found: ')'
+                    }]
+                },
+            ],
+            result
+        );
+    }
+}
@@ -0,0 +1,1206 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use bitvec::prelude::*;
+use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::{CheckedSub, Zero};
+
+use crate::counting::Count;
+
+pub struct Packet {
+    version_number: u8,
+    size: usize, // How many bits long the packet is (included the sub packets).
+    packet_type: PacketType,
+    internal_packets: Option<Vec<Packet>>,
+    data: Option<BigUint>, // Data from literal.
+}
+
+impl Packet {
+    /// Parses the packet starting at bit `start` of the transmission.
+    /// `parent` is the path of the enclosing packet and this packet's index within it, if any.
+    fn new(
+        bits: &BitSlice<Msb0, u8>,
+        start: usize,
+        parent: Option<(&str, usize)>,
+        opcodes: &OpcodeTable,
+    ) -> Result<Self, ParseError> {
+        let mut path = packet_path(parent, "packet");
+        let version_number = read_field(bits, start, 3, Field::Version, &path)? as u8;
+        let type_id = read_field(bits, start + 3, opcodes.type_id_width, Field::TypeId, &path)?;
+        let header_size = 3 + opcodes.type_id_width;
+
+        let packet_type_id = opcodes.packet_type(type_id).ok_or_else(|| ParseError {
+            bit_offset: start + 3,
+            width: opcodes.type_id_width,
+            path: path.clone(),
+            expected: Field::TypeId,
+            kind: ParseErrorKind::UnknownTypeId(type_id),
+        })?;
+
+        let (size, data, internal_packets) = match packet_type_id {
+            PacketType::Literal => {
+                path = packet_path(parent, "literal");
+                // This packet is only containing literal groups, building a number.
+                let mut index = start + header_size; // Index of first bit of the group.
+                let mut number = BigUint::zero();
+                loop {
+                    let group = read_field(bits, index, 5, Field::LiteralGroup, &path)?;
+                    number <<= 4u8;
+                    number += group & 0b1111;
+                    if group & 0b10000 != 0 {
+                        // We have another group.
+                        index += 5;
+                    } else {
+                        break;
+                    }
+                }
+                (index + 5 - start, Some(number), None)
+            }
+            PacketType::Operator(_) => {
+                path = packet_path(parent, "op");
+                // An operator contains multiple internal packets.
+                let length_type_id =
+                    read_field(bits, start + header_size, 1, Field::LengthTypeId, &path)? == 1;
+                let size_field_start = start + header_size + 1;
+                let packets = if length_type_id {
+                    const SIZE_FIELD_SIZE: usize = 11;
+                    let internal_packets_count = read_field(
+                        bits,
+                        size_field_start,
+                        SIZE_FIELD_SIZE,
+                        Field::SubPacketCount,
+                        &path,
+                    )?;
+                    let mut start_of_next_packet = size_field_start + SIZE_FIELD_SIZE;
+                    let mut packets = Vec::new();
+
+                    for i in 0..internal_packets_count {
+                        let next_packet =
+                            Packet::new(bits, start_of_next_packet, Some((&path, i)), opcodes)?;
+                        start_of_next_packet += next_packet.size;
+                        packets.push(next_packet);
+                    }
+                    packets
+                } else {
+                    const SIZE_FIELD_SIZE: usize = 15;
+                    let internal_packets_total_size = read_field(
+                        bits,
+                        size_field_start,
+                        SIZE_FIELD_SIZE,
+                        Field::SubPacketLength,
+                        &path,
+                    )?;
+                    let end_of_packets =
+                        size_field_start + SIZE_FIELD_SIZE + internal_packets_total_size;
+                    let mut start_of_next_packet = size_field_start + SIZE_FIELD_SIZE;
+                    let mut packets = Vec::new();
+                    while start_of_next_packet < end_of_packets {
+                        let next_packet = Packet::new(
+                            bits,
+                            start_of_next_packet,
+                            Some((&path, packets.len())),
+                            opcodes,
+                        )?;
+                        start_of_next_packet += next_packet.size;
+                        packets.push(next_packet);
+                    }
+                    if start_of_next_packet != end_of_packets {
+                        // The last sub packet ran past the declared length.
+                        return Err(ParseError {
+                            bit_offset: end_of_packets,
+                            width: start_of_next_packet - end_of_packets,
+                            path,
+                            expected: Field::SubPacketLength,
+                            kind: ParseErrorKind::SubPacketOverrun {
+                                declared: internal_packets_total_size,
+                                actual: start_of_next_packet - size_field_start - SIZE_FIELD_SIZE,
+                            },
+                        });
+                    }
+
+                    packets
+                };
+
+                (
+                    packets.iter().map(|packet| packet.size).sum::<usize>()
+                        + header_size
+                        + 1
+                        + if length_type_id { 11 } else { 15 },
+                    None,
+                    Some(packets),
+                )
+            }
+        };
+
+        Ok(Self {
+            version_number,
+            size,
+            internal_packets,
+            packet_type: packet_type_id,
+            data,
+        })
+    }
+
+    /// Folds this packet tree bottom-up with the given visitor.
+    pub fn accept<V: PacketVisitor>(&self, visitor: &mut V) -> V::Output {
+        match &self.packet_type {
+            PacketType::Literal => {
+                visitor.visit_literal(self.version_number, self.data.as_ref().unwrap())
+            }
+            PacketType::Operator(operator) => {
+                let operands = self
+                    .internal_packets
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|packet| packet.accept(visitor))
+                    .collect();
+                visitor.visit_operator(self.version_number, *operator, operands)
+            }
+        }
+    }
+
+    pub fn execute<T: Count>(&self) -> Result<T, EvaluationError> {
+        self.accept(&mut Evaluator(PhantomData))
+    }
+}
+
+/// A fold over a packet tree. Operators receive the already visited results of their sub packets.
+pub trait PacketVisitor {
+    type Output;
+
+    fn visit_literal(&mut self, version_number: u8, value: &BigUint) -> Self::Output;
+
+    fn visit_operator(
+        &mut self,
+        version_number: u8,
+        operator: OperatorType,
+        operands: Vec<Self::Output>,
+    ) -> Self::Output;
+}
+
+pub struct VersionSum;
+
+impl PacketVisitor for VersionSum {
+    type Output = u128;
+
+    fn visit_literal(&mut self, version_number: u8, _value: &BigUint) -> Self::Output {
+        version_number as u128
+    }
+
+    fn visit_operator(
+        &mut self,
+        version_number: u8,
+        _operator: OperatorType,
+        operands: Vec<Self::Output>,
+    ) -> Self::Output {
+        version_number as u128 + operands.iter().sum::<u128>()
+    }
+}
+
+struct Evaluator<T>(PhantomData<T>);
+
+impl<T: Count> PacketVisitor for Evaluator<T> {
+    type Output = Result<T, EvaluationError>;
+
+    fn visit_literal(&mut self, _version_number: u8, value: &BigUint) -> Self::Output {
+        T::from_biguint(value).ok_or(EvaluationError::Overflow)
+    }
+
+    fn visit_operator(
+        &mut self,
+        _version_number: u8,
+        operator: OperatorType,
+        operands: Vec<Self::Output>,
+    ) -> Self::Output {
+        let values = operands.into_iter().collect::<Result<Vec<T>, _>>()?;
+
+        operator.apply(values)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DepthStatistics {
+    pub depth: usize, // Number of packets on the longest path from the root to a literal.
+    pub packet_count: usize,
+    pub literal_count: usize,
+}
+
+impl PacketVisitor for DepthStatistics {
+    type Output = DepthStatistics;
+
+    fn visit_literal(&mut self, _version_number: u8, _value: &BigUint) -> Self::Output {
+        DepthStatistics {
+            depth: 1,
+            packet_count: 1,
+            literal_count: 1,
+        }
+    }
+
+    fn visit_operator(
+        &mut self,
+        _version_number: u8,
+        _operator: OperatorType,
+        operands: Vec<Self::Output>,
+    ) -> Self::Output {
+        DepthStatistics {
+            depth: 1 + operands.iter().map(|stats| stats.depth).max().unwrap_or(0),
+            packet_count: 1 + operands
+                .iter()
+                .map(|stats| stats.packet_count)
+                .sum::<usize>(),
+            literal_count: operands.iter().map(|stats| stats.literal_count).sum(),
+        }
+    }
+}
+
+impl DepthStatistics {
+    pub fn of(packet: &Packet) -> Self {
+        packet.accept(&mut DepthStatistics {
+            depth: 0,
+            packet_count: 0,
+            literal_count: 0,
+        })
+    }
+}
+
+/// Counts how often each operator appears in the tree.
+#[derive(Default)]
+pub struct OperatorHistogram {
+    pub counts: HashMap<OperatorType, usize>,
+}
+
+impl PacketVisitor for OperatorHistogram {
+    type Output = ();
+
+    fn visit_literal(&mut self, _version_number: u8, _value: &BigUint) -> Self::Output {}
+
+    fn visit_operator(
+        &mut self,
+        _version_number: u8,
+        operator: OperatorType,
+        _operands: Vec<Self::Output>,
+    ) -> Self::Output {
+        *self.counts.entry(operator).or_default() += 1;
+    }
+}
+
+/// Renders the tree one packet per line, indenting sub packets.
+pub struct PrettyPrinter;
+
+impl PacketVisitor for PrettyPrinter {
+    type Output = Vec<String>;
+
+    fn visit_literal(&mut self, version_number: u8, value: &BigUint) -> Self::Output {
+        vec![format!("v{} Literal {}", version_number, value)]
+    }
+
+    fn visit_operator(
+        &mut self,
+        version_number: u8,
+        operator: OperatorType,
+        operands: Vec<Self::Output>,
+    ) -> Self::Output {
+        std::iter::once(format!("v{} {:?}", version_number, operator))
+            .chain(
+                operands
+                    .into_iter()
+                    .flatten()
+                    .map(|line| format!("  {}", line)),
+            )
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EvaluationError {
+    /// The value didn't fit in the chosen number type.
+    Overflow,
+    /// An operator that needs at least one operand had none.
+    NoOperands(OperatorType),
+    /// An operator didn't have exactly the number of operands it takes.
+    OperandCount {
+        operator: OperatorType,
+        expected: usize,
+        found: usize,
+    },
+    DivisionByZero,
+}
+
+impl std::fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overflow => write!(f, "arithmetic overflow"),
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::NoOperands(operator) => write!(f, "{:?} packet has no operands", operator),
+            Self::OperandCount {
+                operator,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} packet expects {} operands, found {}",
+                operator, expected, found
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Push(usize), // Index into the constant pool.
+    Apply {
+        operator: OperatorType,
+        operands: usize, // How many values to pop off the stack.
+    },
+}
+
+/// A packet tree compiled to flat stack machine code, so it can be evaluated without walking the tree.
+#[derive(Debug, Default)]
+pub struct Program {
+    constants: Vec<BigUint>,
+    code: Vec<Instruction>,
+}
+
+impl PacketVisitor for Program {
+    type Output = ();
+
+    fn visit_literal(&mut self, _version_number: u8, value: &BigUint) -> Self::Output {
+        self.code.push(Instruction::Push(self.constants.len()));
+        self.constants.push(value.clone());
+    }
+
+    fn visit_operator(
+        &mut self,
+        _version_number: u8,
+        operator: OperatorType,
+        operands: Vec<Self::Output>,
+    ) -> Self::Output {
+        self.code.push(Instruction::Apply {
+            operator,
+            operands: operands.len(),
+        });
+    }
+}
+
+impl Program {
+    pub fn compile(packet: &Packet) -> Self {
+        let mut program = Self::default();
+        packet.accept(&mut program);
+        program
+    }
+
+    pub fn run<T: Count>(&self) -> Result<T, EvaluationError> {
+        let mut stack: Vec<T> = Vec::new();
+        for instruction in &self.code {
+            match *instruction {
+                // Convert constants as they are pushed so that errors surface in
+                // the same order as when walking the tree.
+                Instruction::Push(index) => stack.push(
+                    T::from_biguint(&self.constants[index]).ok_or(EvaluationError::Overflow)?,
+                ),
+                Instruction::Apply { operator, operands } => {
+                    let values = stack.split_off(stack.len() - operands);
+                    stack.push(operator.apply(values)?);
+                }
+            }
+        }
+
+        // A compiled tree always leaves exactly its root value behind.
+        Ok(stack.pop().unwrap())
+    }
+
+    pub fn disassemble(&self) -> String {
+        self.code
+            .iter()
+            .enumerate()
+            .map(|(address, instruction)| match instruction {
+                Instruction::Push(index) => {
+                    format!(
+                        "{:04} Push {} ; #{}",
+                        address, self.constants[*index], index
+                    )
+                }
+                Instruction::Apply { operator, operands } => {
+                    format!("{:04} {:?} {}", address, operator, operands)
+                }
+            })
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PacketType {
+    Literal,
+    Operator(OperatorType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperatorType {
+    Sum,
+    Product,
+    Minimum,
+    Maximum,
+    GreaterThan,
+    LessThan,
+    Equal,
+    /// An operator registered alongside the standard ones, see `CustomOperator`.
+    Custom(CustomOperator),
+}
+
+impl OperatorType {
+    /// Applies this operator to the values of its sub packets.
+    fn apply<T: Count>(self, values: Vec<T>) -> Result<T, EvaluationError> {
+        let operator = self;
+        match operator {
+            OperatorType::Sum => values.iter().try_fold(T::zero(), |acc, value| {
+                acc.checked_add(value).ok_or(EvaluationError::Overflow)
+            }),
+            OperatorType::Product => values.iter().try_fold(T::one(), |acc, value| {
+                acc.checked_mul(value).ok_or(EvaluationError::Overflow)
+            }),
+            OperatorType::Minimum => values
+                .into_iter()
+                .min()
+                .ok_or(EvaluationError::NoOperands(operator)),
+            OperatorType::Maximum => values
+                .into_iter()
+                .max()
+                .ok_or(EvaluationError::NoOperands(operator)),
+            OperatorType::Custom(custom) => {
+                if let Some(expected) = custom.arity {
+                    if values.len() != expected {
+                        return Err(EvaluationError::OperandCount {
+                            operator,
+                            expected,
+                            found: values.len(),
+                        });
+                    }
+                }
+                let result = (custom.apply)(values.iter().map(T::to_biguint).collect())?;
+                T::from_biguint(&result).ok_or(EvaluationError::Overflow)
+            }
+            _ => {
+                let found = values.len();
+                let (left, right) =
+                    values
+                        .into_iter()
+                        .collect_tuple()
+                        .ok_or(EvaluationError::OperandCount {
+                            operator,
+                            expected: 2,
+                            found,
+                        })?;
+                let result = match operator {
+                    OperatorType::GreaterThan => left > right,
+                    OperatorType::LessThan => left < right,
+                    OperatorType::Equal => left == right,
+                    _ => unreachable!(),
+                };
+                Ok(if result { T::one() } else { T::zero() })
+            }
+        }
+    }
+}
+
+/// An operator that isn't part of the standard table.
+/// It computes on arbitrary precision operands; a result that doesn't fit the
+/// evaluated number type is an overflow.
+/// Operators are told apart by name, so two operators with the same name are equal.
+#[derive(Clone, Copy)]
+pub struct CustomOperator {
+    name: &'static str,
+    arity: Option<usize>, // None accepts any number of operands.
+    apply: fn(Vec<BigUint>) -> Result<BigUint, EvaluationError>,
+}
+
+impl CustomOperator {
+    pub fn new(
+        name: &'static str,
+        arity: Option<usize>,
+        apply: fn(Vec<BigUint>) -> Result<BigUint, EvaluationError>,
+    ) -> Self {
+        Self { name, arity, apply }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl std::fmt::Debug for CustomOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl PartialEq for CustomOperator {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for CustomOperator {}
+
+impl std::hash::Hash for CustomOperator {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+fn subtract(values: Vec<BigUint>) -> Result<BigUint, EvaluationError> {
+    values[0]
+        .checked_sub(&values[1])
+        .ok_or(EvaluationError::Overflow)
+}
+
+fn modulo(values: Vec<BigUint>) -> Result<BigUint, EvaluationError> {
+    if values[1].is_zero() {
+        return Err(EvaluationError::DivisionByZero);
+    }
+    Ok(&values[0] % &values[1])
+}
+
+fn if_then_else(values: Vec<BigUint>) -> Result<BigUint, EvaluationError> {
+    let (condition, then, otherwise) = values.into_iter().collect_tuple().unwrap();
+    Ok(if condition.is_zero() { otherwise } else { then })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpcodeError {
+    /// The type id can't be encoded in a type id field of this width.
+    TypeIdTooWide {
+        type_id: usize,
+        type_id_width: usize,
+    },
+}
+
+impl std::fmt::Display for OpcodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TypeIdTooWide {
+                type_id,
+                type_id_width,
+            } => write!(
+                f,
+                "type id {} doesn't fit in {} bits",
+                type_id, type_id_width
+            ),
+        }
+    }
+}
+
+/// Maps packet type ids to packet types.
+/// The default is the standard table with a 3 bit type id field.
+pub struct OpcodeTable {
+    type_id_width: usize,
+    opcodes: HashMap<usize, PacketType>,
+}
+
+impl Default for OpcodeTable {
+    fn default() -> Self {
+        Self {
+            type_id_width: 3,
+            opcodes: HashMap::from([
+                (4, PacketType::Literal),
+                (0, PacketType::Operator(OperatorType::Sum)),
+                (1, PacketType::Operator(OperatorType::Product)),
+                (2, PacketType::Operator(OperatorType::Minimum)),
+                (3, PacketType::Operator(OperatorType::Maximum)),
+                (5, PacketType::Operator(OperatorType::GreaterThan)),
+                (6, PacketType::Operator(OperatorType::LessThan)),
+                (7, PacketType::Operator(OperatorType::Equal)),
+            ]),
+        }
+    }
+}
+
+impl OpcodeTable {
+    /// The standard table widened to a 4 bit type id, with subtract, modulo and
+    /// if-then-else registered at 8, 9 and 10.
+    pub fn extended() -> Self {
+        let custom = |name, arity, apply| {
+            PacketType::Operator(OperatorType::Custom(CustomOperator::new(
+                name, arity, apply,
+            )))
+        };
+        Self::default()
+            .with_type_id_width(4)
+            .and_then(|table| table.register(8, custom("Subtract", Some(2), subtract)))
+            .and_then(|table| table.register(9, custom("Modulo", Some(2), modulo)))
+            .and_then(|table| table.register(10, custom("IfThenElse", Some(3), if_then_else)))
+            .expect("Extension opcodes fit in 4 bits")
+    }
+
+    /// Widens (or narrows) the type id field, keeping the registered opcodes.
+    /// Fails if a registered type id no longer fits.
+    pub fn with_type_id_width(mut self, type_id_width: usize) -> Result<Self, OpcodeError> {
+        if let Some(&type_id) = self
+            .opcodes
+            .keys()
+            .filter(|&&type_id| !Self::fits(type_id, type_id_width))
+            .min()
+        {
+            return Err(OpcodeError::TypeIdTooWide {
+                type_id,
+                type_id_width,
+            });
+        }
+        self.type_id_width = type_id_width;
+        Ok(self)
+    }
+
+    /// Maps `type_id` to `packet_type`, replacing any previous mapping.
+    pub fn register(
+        mut self,
+        type_id: usize,
+        packet_type: PacketType,
+    ) -> Result<Self, OpcodeError> {
+        if !Self::fits(type_id, self.type_id_width) {
+            return Err(OpcodeError::TypeIdTooWide {
+                type_id,
+                type_id_width: self.type_id_width,
+            });
+        }
+        self.opcodes.insert(type_id, packet_type);
+        Ok(self)
+    }
+
+    fn fits(type_id: usize, type_id_width: usize) -> bool {
+        type_id_width >= usize::BITS as usize || type_id < 1 << type_id_width
+    }
+
+    fn packet_type(&self, type_id: usize) -> Option<PacketType> {
+        self.opcodes.get(&type_id).copied()
+    }
+}
+
+/// The field the parser was expecting when it failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    HexDigit,
+    Version,
+    TypeId,
+    LengthTypeId,
+    SubPacketLength,
+    SubPacketCount,
+    LiteralGroup,
+}
+
+impl std::fmt::Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::HexDigit => "hex digit",
+            Self::Version => "version",
+            Self::TypeId => "type id",
+            Self::LengthTypeId => "length type id",
+            Self::SubPacketLength => "sub packet length",
+            Self::SubPacketCount => "sub packet count",
+            Self::LiteralGroup => "literal group",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
+    InvalidHex(hex::FromHexError),
+    /// The transmission ended in the middle of a field.
+    Truncated,
+    UnknownTypeId(usize),
+    /// Sub packets ran past the total length given by their operator.
+    SubPacketOverrun {
+        declared: usize,
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHex(error) => write!(f, "invalid hex: {}", error),
+            Self::Truncated => write!(f, "transmission ended early"),
+            Self::UnknownTypeId(type_id) => write!(f, "unknown type id {}", type_id),
+            Self::SubPacketOverrun { declared, actual } => write!(
+                f,
+                "sub packets are {} bits long, but their length was given as {}",
+                actual, declared
+            ),
+        }
+    }
+}
+
+/// Where and why a transmission failed to parse.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub bit_offset: usize,
+    pub width: usize, // How many bits the offending field spans.
+    pub path: String, // e.g. root/op[2]/literal[0]
+    pub expected: Field,
+    pub kind: ParseErrorKind,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at bit {} in {}, expected {}",
+            self.kind, self.bit_offset, self.path, self.expected
+        )
+    }
+}
+
+impl ParseError {
+    /// Renders the error with an excerpt of the input, highlighting the offending bits.
+    pub fn report(&self, input: &str) -> String {
+        let mut lines = vec![
+            format!("error: {}", self.kind),
+            format!(
+                " --> bit {} in {}, expected {}",
+                self.bit_offset, self.path, self.expected
+            ),
+            "  |".to_string(),
+        ];
+
+        let input = input.trim();
+        let bytes = match hex::decode(input) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            _ => {
+                // Nothing to decode, point at the offending character instead.
+                lines.push(format!("  | {}", input));
+                lines.push(format!("  | {}^", " ".repeat(self.bit_offset / 4)));
+                return lines.join("\n");
+            }
+        };
+
+        // Show a few bytes either side of the field.
+        const CONTEXT: usize = 3;
+        let end = self.bit_offset + self.width;
+        let first_byte = (self.bit_offset / 8).saturating_sub(CONTEXT);
+        let last_byte = ((end.max(self.bit_offset + 1) - 1) / 8 + CONTEXT).min(bytes.len() - 1);
+        let window = &bytes[first_byte..=last_byte];
+        let highlighted = (self.bit_offset..end.max(self.bit_offset + 1))
+            .filter(|&bit| bit / 8 >= first_byte && bit / 8 <= last_byte)
+            .collect_vec();
+        let ellipsis = |shown: bool| if shown { "..." } else { "" };
+
+        let mut hex_marks = vec![' '; window.len() * 3];
+        let mut binary_marks = vec![' '; window.len() * 9];
+        for &bit in &highlighted {
+            let byte = bit / 8 - first_byte;
+            hex_marks[byte * 3 + (bit % 8) / 4] = '^';
+            binary_marks[byte * 9 + bit % 8] = '^';
+        }
+        let prefix = ellipsis(first_byte > 0);
+        let suffix = ellipsis(last_byte < bytes.len() - 1);
+        let indent = " ".repeat(prefix.len());
+
+        lines.push(format!(
+            "  | hex     {}{}{}",
+            prefix,
+            window.iter().map(|byte| format!("{:02X}", byte)).join(" "),
+            suffix
+        ));
+        lines.push(format!(
+            "  |         {}{}",
+            indent,
+            hex_marks.iter().collect::<String>().trim_end()
+        ));
+        lines.push(format!(
+            "  | binary  {}{}{}",
+            prefix,
+            window.iter().map(|byte| format!("{:08b}", byte)).join(" "),
+            suffix
+        ));
+        lines.push(format!(
+            "  |         {}{}",
+            indent,
+            binary_marks.iter().collect::<String>().trim_end()
+        ));
+
+        lines.join("\n")
+    }
+}
+
+fn packet_path(parent: Option<(&str, usize)>, kind: &str) -> String {
+    match parent {
+        Some((parent, index)) => format!("{}/{}[{}]", parent, kind, index),
+        None => "root".to_string(),
+    }
+}
+
+/// Reads a big-endian field of `width` bits.
+fn read_field(
+    bits: &BitSlice<Msb0, u8>,
+    offset: usize,
+    width: usize,
+    field: Field,
+    path: &str,
+) -> Result<usize, ParseError> {
+    if offset + width > bits.len() {
+        return Err(ParseError {
+            bit_offset: offset,
+            width,
+            path: path.to_string(),
+            expected: field,
+            kind: ParseErrorKind::Truncated,
+        });
+    }
+
+    Ok(bits[offset..offset + width]
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (*b as usize) << (width - 1 - i))
+        .sum::<usize>())
+}
+
+/// Decodes a hex transmission, ignoring surrounding whitespace such as a trailing newline.
+pub fn parse_transmission(input: &str, opcodes: &OpcodeTable) -> Result<Packet, ParseError> {
+    let input = input.trim();
+    let bytes = hex::decode(input).map_err(|error| {
+        let index = match error {
+            hex::FromHexError::InvalidHexCharacter { index, .. } => index,
+            _ => input.len(),
+        };
+        ParseError {
+            bit_offset: index * 4,
+            width: 4,
+            path: packet_path(None, "packet"),
+            expected: Field::HexDigit,
+            kind: ParseErrorKind::InvalidHex(error),
+        }
+    })?;
+    let bytes = BitVec::<Msb0, u8>::from_slice(&bytes).unwrap();
+
+    Packet::new(&bytes, 0, None, opcodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use test_case::test_case;
+
+    #[test_case("8A004A801A8002F478", 16)]
+    #[test_case("620080001611562C8802118E34", 12)]
+    #[test_case("C0015000016115A2E0802F182340", 23)]
+    #[test_case("A0016C880162017C3686B18A3D4780", 31)]
+    #[test_case("D2FE28", 6)]
+    fn test_version_number_sum(input: &str, expected: u128) {
+        let result = parse_transmission(input, &OpcodeTable::default())
+            .unwrap()
+            .accept(&mut VersionSum);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_depth_statistics_and_histogram() {
+        let input = "9C0141080250320F1802104A08";
+        let packet = parse_transmission(input, &OpcodeTable::default()).unwrap();
+
+        assert_eq!(
+            DepthStatistics {
+                depth: 3,
+                packet_count: 7,
+                literal_count: 4
+            },
+            DepthStatistics::of(&packet)
+        );
+
+        let mut histogram = OperatorHistogram::default();
+        packet.accept(&mut histogram);
+        assert_eq!(
+            HashMap::from([
+                (OperatorType::Equal, 1),
+                (OperatorType::Sum, 1),
+                (OperatorType::Product, 1)
+            ]),
+            histogram.counts
+        );
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        let input = "9C0141080250320F1802104A08";
+        let packet = parse_transmission(input, &OpcodeTable::default()).unwrap();
+
+        let lines = packet.accept(&mut PrettyPrinter);
+
+        assert_eq!(
+            vec![
+                "v4 Equal",
+                "  v2 Sum",
+                "    v2 Literal 1",
+                "    v4 Literal 3",
+                "  v6 Product",
+                "    v0 Literal 2",
+                "    v2 Literal 2",
+            ],
+            lines
+        );
+    }
+
+    #[test_case("110041141060", Ok(7); "subtract 10 and 3")]
+    #[test_case("130041141060", Ok(1); "10 modulo 3")]
+    #[test_case("1500610210A112", Ok(5); "if 1 then 5 else 9")]
+    #[test_case("14004810010A1120", Ok(9); "if 0 then 5 else 9")]
+    #[test_case("010041022200821C2080", Ok(6); "sum 1 and 7 minus 2")]
+    #[test_case("110041061140", Err(EvaluationError::Overflow); "subtract 3 and 10")]
+    #[test_case("130041061000", Err(EvaluationError::DivisionByZero); "3 modulo 0")]
+    fn test_extended_opcodes(input: &str, expected: Result<u128, EvaluationError>) {
+        let packet = parse_transmission(input, &OpcodeTable::extended()).unwrap();
+
+        let result = packet.execute();
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_unregistered_opcode() {
+        // Sum of 1 and an operator with type id 12.
+        let input = "010041023200420C";
+
+        let result = parse_transmission(input, &OpcodeTable::extended());
+
+        assert_eq!(
+            Err(ParseError {
+                bit_offset: 34,
+                width: 4,
+                path: "root/packet[1]".to_string(),
+                expected: Field::TypeId,
+                kind: ParseErrorKind::UnknownTypeId(12),
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    fn power(values: Vec<BigUint>) -> Result<BigUint, EvaluationError> {
+        let exponent = u32::try_from(&values[1]).map_err(|_| EvaluationError::Overflow)?;
+        Ok(values[0].pow(exponent))
+    }
+
+    #[test_case(Some(2), Ok(1000); "10 to the power of 3")]
+    #[test_case(Some(3), Err(3); "wrong arity")]
+    fn test_register_custom_operator(arity: Option<usize>, expected: Result<u128, usize>) {
+        // Type id 8 applied to 10 and 3.
+        let input = "110041141060";
+        let operator = OperatorType::Custom(CustomOperator::new("Power", arity, power));
+        let opcodes = OpcodeTable::default()
+            .with_type_id_width(4)
+            .and_then(|table| table.register(8, PacketType::Operator(operator)))
+            .unwrap();
+        let packet = parse_transmission(input, &opcodes).unwrap();
+
+        let result = packet.execute::<u128>();
+
+        let expected = expected.map_err(|expected| EvaluationError::OperandCount {
+            operator,
+            expected,
+            found: 2,
+        });
+        assert_eq!(expected, result);
+        assert_eq!(result, Program::compile(&packet).run());
+    }
+
+    #[test]
+    fn test_register_type_id_too_wide() {
+        let result = OpcodeTable::default().register(8, PacketType::Literal);
+
+        assert_eq!(
+            Err(OpcodeError::TypeIdTooWide {
+                type_id: 8,
+                type_id_width: 3
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_narrowing_rechecks_registered_type_ids() {
+        let result = OpcodeTable::extended().with_type_id_width(3);
+
+        assert_eq!(
+            Err(OpcodeError::TypeIdTooWide {
+                type_id: 8,
+                type_id_width: 3
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    #[test_case("C200B40A82")]
+    #[test_case("04005AC33890")]
+    #[test_case("880086C3E88112")]
+    #[test_case("CE00C43D881120")]
+    #[test_case("D8005AC2A8F0")]
+    #[test_case("F600BC2D8F")]
+    #[test_case("9C005AC2F8F0")]
+    #[test_case("9C0141080250320F1802104A08")]
+    #[test_case("13FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFDE0")]
+    #[test_case("0600848C210842108421084210009184210842108421084200")]
+    #[test_case("1600C408821060")]
+    #[test_case("0A0000")]
+    #[test_case("8A004A801A8002F478")]
+    #[test_case("620080001611562C8802118E34")]
+    #[test_case("C0015000016115A2E0802F182340")]
+    #[test_case("A0016C880162017C3686B18A3D4780")]
+    #[test_case("D2FE28")]
+    #[test_case("0200858031022084189FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEF")]
+    fn test_vm_matches_execute(input: &str) {
+        let packet = parse_transmission(input, &OpcodeTable::default()).unwrap();
+        let program = Program::compile(&packet);
+
+        assert_eq!(packet.execute::<u128>(), program.run::<u128>());
+        assert_eq!(packet.execute::<BigUint>(), program.run::<BigUint>());
+    }
+
+    #[test_case("110041141060")]
+    #[test_case("130041141060")]
+    #[test_case("1500610210A112")]
+    #[test_case("14004810010A1120")]
+    #[test_case("010041022200821C2080")]
+    #[test_case("110041061140")]
+    #[test_case("130041061000")]
+    fn test_vm_matches_execute_extended(input: &str) {
+        let packet = parse_transmission(input, &OpcodeTable::extended()).unwrap();
+        let program = Program::compile(&packet);
+
+        assert_eq!(packet.execute::<u128>(), program.run::<u128>());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let input = "9C0141080250320F1802104A08";
+        let program =
+            Program::compile(&parse_transmission(input, &OpcodeTable::default()).unwrap());
+
+        let result = program.disassemble();
+
+        assert_eq!(
+            indoc! {"
+                0000 Push 1 ; #0
+                0001 Push 3 ; #1
+                0002 Sum 2
+                0003 Push 2 ; #2
+                0004 Push 2 ; #3
+                0005 Product 2
+                0006 Equal 2"},
+            result
+        );
+    }
+
+    #[test]
+    fn test_truncated_literal() {
+        // Sum of 1, 2 and the product of a literal cut off in its second group.
+        let input = "2200C40882460044D6";
+
+        let result = parse_transmission(input, &OpcodeTable::default());
+
+        assert_eq!(
+            Err(ParseError {
+                bit_offset: 69,
+                width: 5,
+                path: "root/op[2]/literal[0]".to_string(),
+                expected: Field::LiteralGroup,
+                kind: ParseErrorKind::Truncated,
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_sub_packet_overrun() {
+        // Total length of 10 bits, but the first literal takes 11.
+        let input = "000028408820";
+
+        let result = parse_transmission(input, &OpcodeTable::default());
+
+        assert_eq!(
+            Err(ParseError {
+                bit_offset: 32,
+                width: 1,
+                path: "root".to_string(),
+                expected: Field::SubPacketLength,
+                kind: ParseErrorKind::SubPacketOverrun {
+                    declared: 10,
+                    actual: 11
+                },
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_invalid_hex() {
+        let input = "C200B4ZA82";
+
+        let result = parse_transmission(input, &OpcodeTable::default());
+
+        assert_eq!(
+            Err(ParseError {
+                bit_offset: 24,
+                width: 4,
+                path: "root".to_string(),
+                expected: Field::HexDigit,
+                kind: ParseErrorKind::InvalidHex(hex::FromHexError::InvalidHexCharacter {
+                    c: 'Z',
+                    index: 6
+                }),
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_surrounding_whitespace() {
+        let input = "  8A004A801A8002F478\n";
+
+        let result = parse_transmission(input, &OpcodeTable::default())
+            .unwrap()
+            .accept(&mut VersionSum);
+
+        assert_eq!(16, result);
+    }
+
+    #[test]
+    fn test_report_odd_digits_with_trailing_newline() {
+        let input = "C200B40A8\n";
+
+        let error = parse_transmission(input, &OpcodeTable::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            indoc! {"
+                error: invalid hex: Odd number of digits
+                 --> bit 36 in root, expected hex digit
+                  |
+                  | C200B40A8
+                  |          ^"},
+            error.report(input)
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let input = "2200C40882460044D6";
+
+        let error = parse_transmission(input, &OpcodeTable::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            indoc! {"
+                error: transmission ended early
+                 --> bit 69 in root/op[2]/literal[0], expected literal group
+                  |
+                  | hex     ...46 00 44 D6
+                  |                      ^
+                  | binary  ...01000110 00000000 01000100 11010110
+                  |                                            ^^^"},
+            error.report(input)
+        );
+    }
+}
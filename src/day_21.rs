@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use regex::Regex;
+
+/// The rules of a game of Dirac Dice, shared by the deterministic and quantum games.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiracRules {
+    pub board_size: u8,     // Spaces on the track, numbered from 1.
+    pub die_faces: u8,      // The die rolls 1 to die_faces.
+    pub rolls_per_turn: u8, // Rolls summed to move a player each turn.
+    pub win_score: u128,    // A player wins once their score reaches this.
+}
+
+impl DiracRules {
+    /// The rules for part 1, with a 100 sided deterministic die.
+    pub fn deterministic() -> Self {
+        Self {
+            board_size: 10,
+            die_faces: 100,
+            rolls_per_turn: 3,
+            win_score: 1000,
+        }
+    }
+
+    /// The rules for part 2, with a 3 sided Dirac die.
+    pub fn quantum() -> Self {
+        Self {
+            board_size: 10,
+            die_faces: 3,
+            rolls_per_turn: 3,
+            win_score: 21,
+        }
+    }
+
+    /// The space a player on `position` lands on after moving `roll` spaces.
+    pub fn advance(&self, position: u8, roll: u128) -> u8 {
+        let board_size = self.board_size as u128;
+        ((position as u128 - 1 + roll % board_size) % board_size + 1) as u8
+    }
+
+    /// The sum of the rolls for every universe a quantum turn splits into.
+    pub fn roll_sums(&self) -> impl Iterator<Item = u128> {
+        (0..self.rolls_per_turn)
+            .map(|_| 1..=self.die_faces as u128)
+            .multi_cartesian_product()
+            .map(|rolls| rolls.into_iter().sum())
+    }
+
+    /// Each distinct roll sum, with the number of universes that roll it.
+    pub fn roll_sum_multiplicities(&self) -> Vec<(u128, u128)> {
+        self.roll_sums()
+            .counts()
+            .into_iter()
+            .map(|(sum, count)| (sum, count as u128))
+            .sorted()
+            .collect_vec()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Player {
+    pub position: u8, // value from 1 - board size
+    pub score: u128,  // Score from moving
+}
+
+impl Player {
+    pub fn new(position: u8) -> Self {
+        Self { position, score: 0 }
+    }
+
+    pub fn wins(&self, rules: &DiracRules) -> bool {
+        self.score >= rules.win_score
+    }
+
+    pub fn move_player(&mut self, roll: u128, rules: &DiracRules) {
+        self.position = rules.advance(self.position, roll); // Move around the board
+
+        self.score += self.position as u128; // Boost score by position we landed at.
+    }
+}
+
+pub fn parse_players(input: &str) -> Vec<Player> {
+    let re = Regex::new(r#"Player (\d) starting position: (\d+)"#).unwrap();
+
+    input
+        .lines()
+        .map(|s| {
+            let captures = re.captures(s).unwrap();
+            Player::new(captures.get(2).unwrap().as_str().parse::<u8>().unwrap())
+        })
+        .collect_vec()
+}
+
+/// Universe counts for a quantum game.
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuantumSolution {
+    /// `wins_by_round[player][round]` counts the universes where `player` wins on their turn in `round`, from 0.
+    pub wins_by_round: Vec<Vec<u128>>,
+}
+
+impl QuantumSolution {
+    /// The universes each player wins in.
+    pub fn wins(&self) -> Vec<u128> {
+        self.wins_by_round
+            .iter()
+            .map(|rounds| rounds.iter().sum())
+            .collect()
+    }
+
+    /// The universes that end on each turn, where every player's move is a turn.
+    /// Index 0 is the first player's first move.
+    pub fn wins_by_turn(&self) -> Vec<u128> {
+        let players = self.wins_by_round.len();
+        let rounds = self.wins_by_round.iter().map(Vec::len).max().unwrap_or(0);
+
+        (0..rounds * players)
+            .map(|turn| {
+                self.wins_by_round[turn % players]
+                    .get(turn / players)
+                    .copied()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+}
+
+/// One player's universes, counted over their own turns as if they were playing alone.
+struct PlayerTurns {
+    wins: Vec<u128>,           // Universes reaching the win score on each turn.
+    winning_scores: Vec<u128>, // Total final score over those universes.
+    still_playing: Vec<u128>, // Universes yet to win after each turn, starting with the 1 before moving.
+    playing_scores: Vec<u128>, // Total score over those universes.
+}
+
+impl PlayerTurns {
+    /// Steps the distribution of (position, score) one turn at a time until every universe has won.
+    fn new(player: &Player, rules: &DiracRules, multiplicities: &[(u128, u128)]) -> Self {
+        let mut turns = Self {
+            wins: Vec::new(),
+            winning_scores: Vec::new(),
+            still_playing: vec![1],
+            playing_scores: vec![player.score],
+        };
+        let mut states = HashMap::from([(*player, 1u128)]);
+
+        while !states.is_empty() {
+            let mut wins = 0;
+            let mut winning_scores = 0;
+            let mut next_states = HashMap::new();
+            for (state, count) in states {
+                for &(roll, multiplicity) in multiplicities {
+                    let mut next = state;
+                    next.move_player(roll, rules);
+                    if next.wins(rules) {
+                        wins += count * multiplicity;
+                        winning_scores += count * multiplicity * next.score;
+                    } else {
+                        *next_states.entry(next).or_insert(0) += count * multiplicity;
+                    }
+                }
+            }
+            turns.wins.push(wins);
+            turns.winning_scores.push(winning_scores);
+            turns.still_playing.push(next_states.values().sum());
+            turns.playing_scores.push(
+                next_states
+                    .iter()
+                    .map(|(state, count)| state.score * count)
+                    .sum(),
+            );
+            states = next_states;
+        }
+
+        turns
+    }
+
+    fn still_playing_after(&self, turns: usize) -> u128 {
+        self.still_playing.get(turns).copied().unwrap_or(0)
+    }
+}
+
+/// How many turns `other` has had when `player` takes their turn in `round`.
+/// Earlier players have had this round's turn, later ones haven't yet.
+fn turns_taken(other: usize, player: usize, round: usize) -> usize {
+    if other < player {
+        round + 1
+    } else {
+        round
+    }
+}
+
+/// Counts the universes each player wins in, turn by turn.
+///
+/// Players move independently of each other, so each player's universes are counted on their own,
+/// by the distribution of (position, score) after each of their turns. A player wins on a turn in the
+/// universes where they reach the win score, times the universes where no other player has won yet.
+pub fn solve_quantum(players: &[Player], rules: &DiracRules) -> QuantumSolution {
+    let multiplicities = rules.roll_sum_multiplicities();
+    let turns = players
+        .iter()
+        .map(|player| PlayerTurns::new(player, rules, &multiplicities))
+        .collect_vec();
+
+    let wins_by_round = turns
+        .iter()
+        .enumerate()
+        .map(|(player, player_turns)| {
+            player_turns
+                .wins
+                .iter()
+                .enumerate()
+                .map(|(round, wins)| {
+                    let others: u128 = turns
+                        .iter()
+                        .enumerate()
+                        .filter(|&(other, _)| other != player)
+                        .map(|(other, other_turns)| {
+                            other_turns.still_playing_after(turns_taken(other, player, round))
+                        })
+                        .product();
+                    wins * others
+                })
+                .collect()
+        })
+        .collect();
+
+    QuantumSolution { wins_by_round }
+}
+
+/// Probabilities for a quantum game played with a fair die.
+#[derive(Debug)]
+pub struct QuantumAnalysis {
+    pub win_probability: Vec<f64>,
+    /// The probability of the game ending on each turn, where every player's move is a turn.
+    /// Index 0 is the first player's first move.
+    pub length_distribution: Vec<f64>,
+    pub expected_scores: Vec<f64>, // Expected score of each player when the game ends.
+}
+
+impl QuantumAnalysis {
+    pub fn expected_turns(&self) -> f64 {
+        self.length_distribution
+            .iter()
+            .enumerate()
+            .map(|(turn, probability)| (turn + 1) as f64 * probability)
+            .sum()
+    }
+}
+
+/// Works out the odds of a quantum game.
+///
+/// Universe counts aren't probabilities, as a game that ends sooner splits into fewer universes.
+/// Each count is weighted by the chance of rolling its way there, one in `outcomes` per turn.
+pub fn analyse_quantum(players: &[Player], rules: &DiracRules) -> QuantumAnalysis {
+    let multiplicities = rules.roll_sum_multiplicities();
+    let outcomes = multiplicities.iter().map(|&(_, count)| count).sum::<u128>() as f64;
+    let probability = |count: u128, turns: usize| count as f64 / outcomes.powi(turns as i32);
+
+    let turns = players
+        .iter()
+        .map(|player| PlayerTurns::new(player, rules, &multiplicities))
+        .collect_vec();
+    let rounds = turns.iter().map(|t| t.wins.len()).max().unwrap_or(0);
+
+    // The chance that `other` hasn't won by the time `player` moves in `round`, and their expected score then.
+    let still_playing = |other: usize, player: usize, round: usize| {
+        let taken = turns_taken(other, player, round);
+        probability(turns[other].still_playing_after(taken), taken)
+    };
+    let playing_score = |other: usize, player: usize, round: usize| {
+        let taken = turns_taken(other, player, round);
+        turns[other]
+            .playing_scores
+            .get(taken)
+            .map_or(0.0, |&score| probability(score, taken))
+    };
+
+    let mut analysis = QuantumAnalysis {
+        win_probability: vec![0.0; players.len()],
+        length_distribution: vec![0.0; rounds * players.len()],
+        expected_scores: vec![0.0; players.len()],
+    };
+    for (player, player_turns) in turns.iter().enumerate() {
+        for (round, &wins) in player_turns.wins.iter().enumerate() {
+            let wins = probability(wins, round + 1);
+            let others = (0..players.len()).filter(|&other| other != player);
+            let nobody_else_won = others
+                .clone()
+                .map(|other| still_playing(other, player, round))
+                .product::<f64>();
+
+            analysis.win_probability[player] += wins * nobody_else_won;
+            analysis.length_distribution[round * players.len() + player] += wins * nobody_else_won;
+            analysis.expected_scores[player] +=
+                probability(player_turns.winning_scores[round], round + 1) * nobody_else_won;
+            for other in others.clone() {
+                let rest = others
+                    .clone()
+                    .filter(|&rest| rest != other)
+                    .map(|rest| still_playing(rest, player, round))
+                    .product::<f64>();
+                analysis.expected_scores[other] +=
+                    wins * playing_score(other, player, round) * rest;
+            }
+        }
+    }
+
+    analysis
+}
+
+/// The first player's chance of winning a two player game, for every pair of starting spaces.
+/// `matrix[a - 1][b - 1]` is the chance when the first player starts on `a` and the second on `b`.
+pub fn start_position_matrix(rules: &DiracRules) -> Vec<Vec<f64>> {
+    (1..=rules.board_size)
+        .map(|first| {
+            (1..=rules.board_size)
+                .map(|second| {
+                    let players = [Player::new(first), Player::new(second)];
+                    analyse_quantum(&players, rules).win_probability[0]
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(10, 6, 6)]
+    #[test_case(7, 3, 10)]
+    #[test_case(4, 20, 4)]
+    #[test_case(1, 9, 10)]
+    fn test_advance(position: u8, roll: u128, expected: u8) {
+        let rules = DiracRules::quantum();
+
+        let result = rules.advance(position, roll);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_advance_larger_board() {
+        let rules = DiracRules {
+            board_size: 20,
+            ..DiracRules::quantum()
+        };
+
+        assert_eq!(3, rules.advance(18, 5));
+        assert_eq!(20, rules.advance(18, 2));
+    }
+
+    #[test]
+    fn test_roll_sums() {
+        let rules = DiracRules::quantum();
+
+        let counts = rules.roll_sums().counts();
+
+        assert_eq!(27, rules.roll_sums().count());
+        assert_eq!(
+            vec![(3, 1), (4, 3), (5, 6), (6, 7), (7, 6), (8, 3), (9, 1)],
+            counts.into_iter().sorted().collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_roll_sum_multiplicities() {
+        let rules = DiracRules::quantum();
+
+        let result = rules.roll_sum_multiplicities();
+
+        assert_eq!(
+            vec![(3, 1), (4, 3), (5, 6), (6, 7), (7, 6), (8, 3), (9, 1)],
+            result
+        );
+    }
+
+    #[test]
+    fn test_solve_quantum_example() {
+        let players = [Player::new(4), Player::new(8)];
+
+        let result = solve_quantum(&players, &DiracRules::quantum());
+
+        assert_eq!(vec![444356092776315, 341960390180808], result.wins());
+        assert_eq!(
+            result.wins().iter().sum::<u128>(),
+            result.wins_by_turn().iter().sum::<u128>()
+        );
+    }
+
+    #[test]
+    fn test_solve_quantum_three_players() {
+        let players = [Player::new(4), Player::new(8), Player::new(1)];
+        let rules = DiracRules {
+            die_faces: 2,
+            rolls_per_turn: 2,
+            win_score: 10,
+            ..DiracRules::quantum()
+        };
+
+        let result = solve_quantum(&players, &rules);
+
+        assert_eq!(vec![720, 4, 624], result.wins());
+    }
+
+    #[test]
+    fn test_solve_quantum_wins_by_turn() {
+        // From space 1 with a d2, a player is on (2, 2) or (3, 3) after one turn.
+        // All but the universe landing on (3, 5) then win on their second turn.
+        let players = [Player::new(1), Player::new(1)];
+        let rules = DiracRules {
+            die_faces: 2,
+            rolls_per_turn: 1,
+            win_score: 6,
+            ..DiracRules::quantum()
+        };
+
+        let result = solve_quantum(&players, &rules);
+
+        assert_eq!(vec![vec![0, 6, 2], vec![0, 3, 0]], result.wins_by_round);
+        assert_eq!(vec![0, 0, 6, 3, 2, 0], result.wins_by_turn());
+    }
+
+    fn assert_close(expected: &[f64], result: &[f64]) {
+        assert_eq!(expected.len(), result.len());
+        for (expected, result) in expected.iter().zip(result) {
+            assert!(
+                (expected - result).abs() < 1e-9,
+                "{:?} != {:?}",
+                expected,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_analyse_quantum_by_hand() {
+        // The same game as test_solve_quantum_wins_by_turn, where each turn halves the odds.
+        let players = [Player::new(1), Player::new(1)];
+        let rules = DiracRules {
+            die_faces: 2,
+            rolls_per_turn: 1,
+            win_score: 6,
+            ..DiracRules::quantum()
+        };
+
+        let result = analyse_quantum(&players, &rules);
+
+        assert_close(&[13.0 / 16.0, 3.0 / 16.0], &result.win_probability);
+        assert_close(
+            &[0.0, 0.0, 3.0 / 4.0, 3.0 / 16.0, 1.0 / 16.0, 0.0],
+            &result.length_distribution,
+        );
+        assert_close(&[53.0 / 16.0], &[result.expected_turns()]);
+        assert_close(&[217.0 / 32.0, 7.0 / 2.0], &result.expected_scores);
+    }
+
+    #[test]
+    fn test_analyse_quantum_example() {
+        let players = [Player::new(4), Player::new(8)];
+
+        let result = analyse_quantum(&players, &DiracRules::quantum());
+
+        assert_close(
+            &[0.7760439136743383, 1.0 - 0.7760439136743383],
+            &result.win_probability,
+        );
+        assert_close(&[6.918711699298747], &[result.expected_turns()]);
+        assert_close(
+            &[22.218666875424717, 16.000658243528292],
+            &result.expected_scores,
+        );
+    }
+
+    #[test]
+    fn test_start_position_matrix() {
+        let result = start_position_matrix(&DiracRules::quantum());
+
+        assert_eq!(10, result.len());
+        assert!(result.iter().all(|row| row.len() == 10));
+        assert_close(&[0.7760439136743383], &[result[3][7]]);
+    }
+}
@@ -0,0 +1,368 @@
+use itertools::Itertools;
+
+/// A way to win, as the lines of cells that must all be marked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WinPattern {
+    Rows,
+    Columns,
+    /// Both corner to corner diagonals, on square boards.
+    Diagonals,
+    FourCorners,
+    /// Every cell.
+    Blackout,
+    /// The marked cells of a mask the same size as the board, row by row.
+    Mask {
+        width: usize,
+        height: usize,
+        cells: Vec<bool>,
+    },
+}
+
+impl WinPattern {
+    /// The standard rules, rows and columns.
+    pub fn standard() -> Vec<WinPattern> {
+        vec![WinPattern::Rows, WinPattern::Columns]
+    }
+
+    /// Reads a mask drawn with `X` for cells that must be marked, and anything else for cells
+    /// that don't matter.
+    pub fn mask(drawing: &str) -> Self {
+        let rows = drawing
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect_vec();
+        let width = rows[0].chars().count();
+        assert!(
+            rows.iter().all(|row| row.chars().count() == width),
+            "Mask rows differ in width!"
+        );
+
+        Self::Mask {
+            width,
+            height: rows.len(),
+            cells: rows
+                .iter()
+                .flat_map(|row| row.chars())
+                .map(|c| c == 'X')
+                .collect(),
+        }
+    }
+
+    /// The cells of each line for a board, as indices into the board.
+    fn lines(&self, width: usize, height: usize) -> Vec<Vec<usize>> {
+        match self {
+            WinPattern::Rows => (0..height)
+                .map(|y| (0..width).map(|x| y * width + x).collect())
+                .collect(),
+            WinPattern::Columns => (0..width)
+                .map(|x| (0..height).map(|y| y * width + x).collect())
+                .collect(),
+            WinPattern::Diagonals if width == height => vec![
+                (0..width).map(|i| i * width + i).collect(),
+                (0..width).map(|i| i * width + (width - 1 - i)).collect(),
+            ],
+            WinPattern::Diagonals => Vec::new(),
+            WinPattern::FourCorners => {
+                vec![vec![0, width - 1, (height - 1) * width, height * width - 1]
+                    .into_iter()
+                    .unique()
+                    .collect()]
+            }
+            WinPattern::Blackout => vec![(0..width * height).collect()],
+            WinPattern::Mask {
+                width: mask_width,
+                height: mask_height,
+                cells,
+            } if (*mask_width, *mask_height) == (width, height) => {
+                vec![cells.iter().positions(|&marked| marked).collect()]
+            }
+            // A mask for some other size of board can't be won.
+            WinPattern::Mask { .. } => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    width: usize,
+    height: usize,
+    board_numbers: Vec<(u32, bool)>, // Row by row.
+}
+
+impl Board {
+    /// Reads rows of whitespace separated numbers, which must all be as wide as each other.
+    pub fn parse(rows: &[&str]) -> Self {
+        let rows = rows
+            .iter()
+            .map(|row| {
+                row.split_whitespace()
+                    .map(|s| s.parse::<u32>().unwrap())
+                    .collect_vec()
+            })
+            .collect_vec();
+        let width = rows[0].len();
+        assert!(
+            rows.iter().all(|row| row.len() == width),
+            "Board rows differ in width!"
+        );
+
+        Self {
+            width,
+            height: rows.len(),
+            board_numbers: rows.into_iter().flatten().map(|n| (n, false)).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn unmarked_sum(&self) -> u32 {
+        self.board_numbers
+            .iter()
+            .filter(|(_, x)| !x)
+            .map(|x| x.0)
+            .sum()
+    }
+
+    /// Marks `number`, and returns whether the board now has a line from any of `patterns`.
+    pub fn mark(&mut self, number: u32, patterns: &[WinPattern]) -> bool {
+        let mut found = false;
+        for cell in self.board_numbers.iter_mut().filter(|x| x.0 == number) {
+            cell.1 = true;
+            found = true;
+        }
+
+        found && self.check_wins(patterns)
+    }
+
+    fn check_wins(&self, patterns: &[WinPattern]) -> bool {
+        patterns
+            .iter()
+            .flat_map(|pattern| pattern.lines(self.width, self.height))
+            .any(|line| line.iter().all(|&i| self.board_numbers[i].1))
+    }
+}
+
+/// Reads the numbers to draw, then boards separated by blank lines.
+pub fn parse(input: &str) -> (Vec<u32>, Vec<Board>) {
+    let mut lines = input.lines();
+    let seq = if let Some(l) = lines.next() {
+        l.split_terminator(',')
+            .map(|s| s.trim().parse::<u32>().unwrap())
+            .collect_vec()
+    } else {
+        panic!("Malformed first line!");
+    };
+
+    let boards = lines
+        .map(str::trim)
+        .group_by(|line| line.is_empty())
+        .into_iter()
+        .filter(|(blank, _)| !blank)
+        .map(|(_, rows)| Board::parse(&rows.collect_vec()))
+        .collect();
+
+    (seq, boards)
+}
+
+/// A board getting bingo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Win {
+    pub board: usize,      // Index of the board in the input.
+    pub draw: usize,       // Index of the winning number in the sequence.
+    pub number: u32,       // The winning number.
+    pub unmarked_sum: u32, // Sum of the board's unmarked numbers when it won.
+    pub score: u32,
+}
+
+/// Every bingo in a game, in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timeline {
+    pub wins: Vec<Win>,
+    pub never_won: Vec<usize>, // Boards that were still playing when the numbers ran out.
+}
+
+impl Timeline {
+    pub fn first(&self) -> Option<&Win> {
+        self.wins.first()
+    }
+
+    pub fn last(&self) -> Option<&Win> {
+        self.wins.last()
+    }
+}
+
+/// Draws every number in `seq`, recording each board's first bingo.
+///
+/// Boards that win on the same number are in the order they appear in the input.
+pub fn simulate_boards(seq: &[u32], boards: &mut [Board], patterns: &[WinPattern]) -> Timeline {
+    let mut timeline = Timeline::default();
+    let mut playing = vec![true; boards.len()];
+
+    for (draw, &number) in seq.iter().enumerate() {
+        for (i, board) in boards.iter_mut().enumerate() {
+            if playing[i] && board.mark(number, patterns) {
+                playing[i] = false;
+                let unmarked_sum = board.unmarked_sum();
+                timeline.wins.push(Win {
+                    board: i,
+                    draw,
+                    number,
+                    unmarked_sum,
+                    score: number * unmarked_sum,
+                });
+            }
+        }
+    }
+    timeline.never_won = playing.iter().positions(|&playing| playing).collect();
+
+    timeline
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use test_case::test_case;
+
+    const SMALL: &str = indoc! {"1,2,3,4,5,6,7,8,9
+
+        1 2 3
+        4 5 6
+
+        9 8
+        7 6
+        5 4
+        "};
+
+    fn marked(board: &mut Board, numbers: &[u32], patterns: &[WinPattern]) -> Vec<bool> {
+        numbers
+            .iter()
+            .map(|&number| board.mark(number, patterns))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_dimensions() {
+        let (sequence, boards) = parse(SMALL);
+
+        assert_eq!(9, sequence.len());
+        assert_eq!(
+            vec![(3, 2), (2, 3)],
+            boards
+                .iter()
+                .map(|board| (board.width(), board.height()))
+                .collect_vec()
+        );
+    }
+
+    #[test_case(&[1, 2, 3], WinPattern::Rows, vec![false, false, true])]
+    #[test_case(&[2, 5], WinPattern::Columns, vec![false, true])]
+    #[test_case(&[1, 3, 4, 6], WinPattern::FourCorners, vec![false, false, false, true])]
+    #[test_case(&[1, 2, 3, 4, 5], WinPattern::Blackout, vec![false; 5])]
+    #[test_case(&[1, 5, 3], WinPattern::Diagonals, vec![false; 3])]
+    fn test_win_patterns(numbers: &[u32], pattern: WinPattern, expected: Vec<bool>) {
+        let (_, mut boards) = parse(SMALL);
+
+        let result = marked(&mut boards[0], numbers, &[pattern]);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_diagonals() {
+        let mut board = Board::parse(&["1 2 3", "4 5 6", "7 8 9"]);
+
+        let result = marked(&mut board, &[3, 1, 5, 7], &[WinPattern::Diagonals]);
+
+        assert_eq!(vec![false, false, false, true], result);
+    }
+
+    #[test]
+    fn test_mask() {
+        let mut board = Board::parse(&["1 2 3", "4 5 6", "7 8 9"]);
+        let plus = WinPattern::mask(indoc! {"
+            .X.
+            XXX
+            .X."});
+
+        let result = marked(&mut board, &[2, 4, 5, 6, 1, 8], &[plus]);
+
+        assert_eq!(vec![false, false, false, false, false, true], result);
+    }
+
+    #[test]
+    fn test_mask_other_size() {
+        let mut board = Board::parse(&["1 2", "3 4"]);
+        let plus = WinPattern::mask(".X.\nXXX\n.X.");
+
+        let result = marked(&mut board, &[1, 2, 3, 4], &[plus]);
+
+        assert_eq!(vec![false; 4], result);
+    }
+
+    #[test]
+    fn test_timeline() {
+        let input = indoc! {"7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+        22 13 17 11  0
+         8  2 23  4 24
+        21  9 14 16  7
+         6 10  3 18  5
+         1 12 20 15 19
+
+         3 15  0  2 22
+         9 18 13 17  5
+        19  8  7 25 23
+        20 11 10 24  4
+        14 21 16 12  6
+
+        14 21 17 24  4
+        10 16 15  9 19
+        18  8 23 26 20
+        22 11 13  6  5
+         2  0 12  3  7
+        "};
+        let (sequence, mut boards) = parse(input);
+
+        let result = simulate_boards(&sequence, &mut boards, &WinPattern::standard());
+
+        let win = |board, draw, number, unmarked_sum| Win {
+            board,
+            draw,
+            number,
+            unmarked_sum,
+            score: number * unmarked_sum,
+        };
+        assert_eq!(
+            Timeline {
+                wins: vec![
+                    win(2, 11, 24, 188),
+                    win(0, 13, 16, 137),
+                    win(1, 14, 13, 148)
+                ],
+                never_won: Vec::new(),
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_never_won() {
+        let (sequence, mut boards) = parse(SMALL);
+
+        let result = simulate_boards(&sequence[..3], &mut boards, &WinPattern::standard());
+
+        assert_eq!(
+            vec![0],
+            result.wins.iter().map(|win| win.board).collect_vec()
+        );
+        assert_eq!(vec![1], result.never_won);
+    }
+}
@@ -0,0 +1,353 @@
+use itertools::Itertools;
+
+/// How much fuel a crab burns to move some distance.
+pub trait FuelCost {
+    fn fuel(&self, distance: u64) -> u64;
+
+    /// Positions known to contain the leftmost optimum, if there's a closed form for them.
+    fn candidates(&self, _crabs: &[u64]) -> Option<Vec<u64>> {
+        None
+    }
+}
+
+/// One fuel per step, as in part 1. The median is optimal.
+pub struct Linear;
+
+impl FuelCost for Linear {
+    fn fuel(&self, distance: u64) -> u64 {
+        distance
+    }
+
+    fn candidates(&self, crabs: &[u64]) -> Option<Vec<u64>> {
+        let sorted = crabs.iter().copied().sorted().collect_vec();
+        Some(vec![sorted[(sorted.len() - 1) / 2]])
+    }
+}
+
+/// Each step costs one more than the last, as in part 2.
+///
+/// The optimum is within half a step of the mean.
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn fuel(&self, distance: u64) -> u64 {
+        distance * (distance + 1) / 2
+    }
+
+    fn candidates(&self, crabs: &[u64]) -> Option<Vec<u64>> {
+        let (sum, count) = (crabs.iter().sum::<u64>(), crabs.len() as u64);
+        // floor(mean - 1/2) to ceil(mean + 1/2)
+        let lower = (2 * sum).saturating_sub(count) / (2 * count);
+        let upper = (2 * sum + count).div_ceil(2 * count);
+        Some((lower..=upper).collect())
+    }
+}
+
+/// Fuel is the square of the distance, so the optimum is next to the mean.
+pub struct Quadratic;
+
+impl FuelCost for Quadratic {
+    fn fuel(&self, distance: u64) -> u64 {
+        distance * distance
+    }
+
+    fn candidates(&self, crabs: &[u64]) -> Option<Vec<u64>> {
+        let (sum, count) = (crabs.iter().sum::<u64>(), crabs.len() as u64);
+        Some(vec![sum / count, sum.div_ceil(count)])
+    }
+}
+
+/// Any other cost, e.g. `|distance| distance.pow(3)`.
+impl<F: Fn(u64) -> u64> FuelCost for F {
+    fn fuel(&self, distance: u64) -> u64 {
+        self(distance)
+    }
+}
+
+/// Where to line the crabs up, and what it costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: u64,
+    pub cost: u64,
+}
+
+pub fn parse_crabs(input: &str) -> Vec<u64> {
+    input
+        .split_terminator(',')
+        .map(|s| s.trim().parse::<u64>().unwrap())
+        .collect_vec()
+}
+
+/// Total fuel for every crab to move to `position`.
+pub fn total_cost(crabs: &[u64], position: u64, cost: &impl FuelCost) -> u64 {
+    crabs
+        .iter()
+        .map(|&crab| cost.fuel(crab.abs_diff(position)))
+        .sum()
+}
+
+/// The cheapest of `positions`, preferring the leftmost on a tie.
+fn cheapest(
+    crabs: &[u64],
+    positions: impl IntoIterator<Item = u64>,
+    cost: &impl FuelCost,
+) -> Alignment {
+    positions
+        .into_iter()
+        .map(|position| Alignment {
+            position,
+            cost: total_cost(crabs, position, cost),
+        })
+        .min_by_key(|alignment| (alignment.cost, alignment.position))
+        .unwrap()
+}
+
+/// Checks the cost never gets cheaper per step as the distance grows, up to `max_distance`.
+pub fn is_convex(cost: &impl FuelCost, max_distance: u64) -> bool {
+    (0..=max_distance)
+        .map(|distance| cost.fuel(distance))
+        .tuple_windows()
+        .map(|(a, b)| b as i128 - a as i128)
+        .tuple_windows()
+        .all(|(a, b)| a <= b)
+}
+
+/// Binary searches for the leftmost optimum on the slope of the total cost.
+///
+/// This only works when the cost is convex, so otherwise every position is checked instead.
+pub fn convex_search(crabs: &[u64], cost: &impl FuelCost) -> Alignment {
+    let (min, max) = crabs.iter().copied().minmax().into_option().unwrap();
+    if !is_convex(cost, max - min) {
+        return cheapest(crabs, min..=max, cost);
+    }
+
+    let (mut lower, mut upper) = (min, max);
+    while lower != upper {
+        let left = (lower + upper) / 2;
+        let right = left + 1;
+
+        if total_cost(crabs, left, cost) <= total_cost(crabs, right, cost) {
+            // right is no better than left, so we look left,
+            upper = left;
+        } else {
+            // left was greater than right, so we look right,
+            lower = right;
+        }
+    }
+
+    Alignment {
+        position: lower,
+        cost: total_cost(crabs, lower, cost),
+    }
+}
+
+/// The cheapest alignment, from the closed form candidates if the cost has them.
+pub fn best_alignment(crabs: &[u64], cost: &impl FuelCost) -> Alignment {
+    match cost.candidates(crabs) {
+        Some(candidates) => cheapest(crabs, candidates, cost),
+        None => convex_search(crabs, cost),
+    }
+}
+
+/// The alignment at every position from the leftmost to the rightmost crab.
+pub fn cost_curve(crabs: &[u64], cost: &impl FuelCost) -> Vec<Alignment> {
+    let (min, max) = crabs.iter().copied().minmax().into_option().unwrap();
+    (min..=max)
+        .map(|position| Alignment {
+            position,
+            cost: total_cost(crabs, position, cost),
+        })
+        .collect()
+}
+
+/// Every position on the curve that ties for the cheapest.
+pub fn tied_optima(curve: &[Alignment]) -> Vec<Alignment> {
+    let cheapest = curve.iter().map(|alignment| alignment.cost).min();
+    curve
+        .iter()
+        .filter(|alignment| Some(alignment.cost) == cheapest)
+        .copied()
+        .collect()
+}
+
+/// The `k` cheapest positions on the curve, leftmost first on a tie.
+pub fn top_k(curve: &[Alignment], k: usize) -> Vec<Alignment> {
+    curve
+        .iter()
+        .copied()
+        .sorted_by_key(|alignment| (alignment.cost, alignment.position))
+        .take(k)
+        .collect()
+}
+
+/// The linear and triangular cost curves side by side, one position per row.
+pub fn cost_curve_csv(crabs: &[u64]) -> String {
+    let linear = cost_curve(crabs, &Linear);
+    let triangular = cost_curve(crabs, &Triangular);
+
+    let mut csv = String::from("position,linear,triangular\n");
+    for (linear, triangular) in linear.iter().zip(triangular.iter()) {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            linear.position, linear.cost, triangular.cost
+        ));
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "16,1,2,0,4,2,7,1,2,14";
+
+    fn brute_force(crabs: &[u64], cost: &impl FuelCost) -> Alignment {
+        let max = *crabs.iter().max().unwrap();
+        cheapest(crabs, 0..=max, cost)
+    }
+
+    #[test]
+    fn test_example() {
+        let crabs = parse_crabs(EXAMPLE);
+
+        assert_eq!(
+            Alignment {
+                position: 2,
+                cost: 37
+            },
+            best_alignment(&crabs, &Linear)
+        );
+        assert_eq!(
+            Alignment {
+                position: 5,
+                cost: 168
+            },
+            best_alignment(&crabs, &Triangular)
+        );
+    }
+
+    #[test]
+    fn test_closed_forms_match_search() {
+        let inputs = [
+            EXAMPLE,
+            "0,0,0,0,0,0,0,0,0,1000",
+            "3",
+            "1,2",
+            "5,5,9,100,100,101,2,0,0,0,7",
+            "999,1,998,2,997",
+        ];
+        for input in inputs {
+            let crabs = parse_crabs(input);
+
+            assert_eq!(
+                brute_force(&crabs, &Linear),
+                best_alignment(&crabs, &Linear)
+            );
+            assert_eq!(
+                brute_force(&crabs, &Triangular),
+                best_alignment(&crabs, &Triangular)
+            );
+            assert_eq!(
+                brute_force(&crabs, &Quadratic),
+                best_alignment(&crabs, &Quadratic)
+            );
+            assert_eq!(brute_force(&crabs, &Linear), convex_search(&crabs, &Linear));
+            assert_eq!(
+                brute_force(&crabs, &Triangular),
+                convex_search(&crabs, &Triangular)
+            );
+        }
+    }
+
+    #[test]
+    fn test_user_defined_cost() {
+        let crabs = parse_crabs(EXAMPLE);
+        let cubic = |distance: u64| distance.pow(3);
+
+        assert!(is_convex(&cubic, 16));
+        assert_eq!(brute_force(&crabs, &cubic), best_alignment(&crabs, &cubic));
+    }
+
+    #[test]
+    fn test_non_convex_cost() {
+        // Steps past the third are free, so the search can't trust the slope.
+        let crabs = parse_crabs("0,0,1,10,10");
+        let capped = |distance: u64| distance.min(3);
+
+        let result = best_alignment(&crabs, &capped);
+
+        assert!(!is_convex(&capped, 10));
+        assert_eq!(brute_force(&crabs, &capped), result);
+    }
+
+    #[test]
+    fn test_cost_curve_csv() {
+        let crabs = parse_crabs("1,3,4");
+
+        let result = cost_curve_csv(&crabs);
+
+        assert_eq!(
+            "position,linear,triangular\n1,5,9\n2,4,5\n3,3,4\n4,4,7\n",
+            result
+        );
+    }
+
+    #[test]
+    fn test_tied_optima() {
+        // Any position between the middle two crabs is as good as the median.
+        let crabs = parse_crabs("0,2,6,9");
+        let curve = cost_curve(&crabs, &Linear);
+
+        let result = tied_optima(&curve)
+            .iter()
+            .map(|alignment| alignment.position)
+            .collect_vec();
+
+        assert_eq!(vec![2, 3, 4, 5, 6], result);
+        assert_eq!(best_alignment(&crabs, &Linear), tied_optima(&curve)[0]);
+    }
+
+    #[test]
+    fn test_top_k() {
+        let crabs = parse_crabs(EXAMPLE);
+        let curve = cost_curve(&crabs, &Triangular);
+
+        let result = top_k(&curve, 3);
+
+        assert_eq!(
+            vec![
+                Alignment {
+                    position: 5,
+                    cost: 168
+                },
+                Alignment {
+                    position: 4,
+                    cost: 170
+                },
+                Alignment {
+                    position: 6,
+                    cost: 176
+                },
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_search_finds_global_minimum_on_skewed_inputs() {
+        let inputs = [
+            "0,0,0,0,0,0,0,0,0,0,0,0,1000",
+            "0,1000,1000,1000,1000,1000,1000",
+            "1,1,2,3,5,8,13,21,34,55,89,144,233,377,610,987",
+        ];
+        for input in inputs {
+            let crabs = parse_crabs(input);
+            let linear = tied_optima(&cost_curve(&crabs, &Linear));
+            let triangular = tied_optima(&cost_curve(&crabs, &Triangular));
+
+            assert_eq!(linear[0], convex_search(&crabs, &Linear));
+            assert_eq!(triangular[0], convex_search(&crabs, &Triangular));
+        }
+    }
+}
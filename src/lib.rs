@@ -0,0 +1,7 @@
+pub mod counting;
+pub mod day_10;
+pub mod day_16;
+pub mod day_21;
+pub mod day_4;
+pub mod day_7;
+pub mod matrix;
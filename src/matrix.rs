@@ -0,0 +1,113 @@
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Square matrix of counts, indexed `[to][from]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matrix {
+    pub cells: Vec<Vec<BigUint>>,
+}
+
+impl Matrix {
+    pub fn identity(size: usize) -> Self {
+        let cells = (0..size)
+            .map(|row| {
+                (0..size)
+                    .map(|column| match row == column {
+                        true => BigUint::one(),
+                        false => BigUint::zero(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { cells }
+    }
+
+    pub fn multiply(&self, other: &Matrix, modulus: Option<&BigUint>) -> Self {
+        let size = self.cells.len();
+        let cells = (0..size)
+            .map(|row| {
+                (0..size)
+                    .map(|column| {
+                        let cell = (0..size)
+                            .map(|k| &self.cells[row][k] * &other.cells[k][column])
+                            .sum::<BigUint>();
+                        match modulus {
+                            Some(modulus) => cell % modulus,
+                            None => cell,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { cells }
+    }
+
+    /// Raises by squaring, so this takes O(log exponent) multiplications.
+    pub fn pow(&self, mut exponent: u64, modulus: Option<&BigUint>) -> Self {
+        let mut result = Matrix::identity(self.cells.len());
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.multiply(&base, modulus);
+            }
+            base = base.multiply(&base, modulus);
+            exponent >>= 1;
+        }
+
+        result
+    }
+
+    pub fn apply(&self, counts: &[BigUint], modulus: Option<&BigUint>) -> Vec<BigUint> {
+        self.cells
+            .iter()
+            .map(|row| {
+                let count = row.iter().zip(counts).map(|(a, b)| a * b).sum::<BigUint>();
+                match modulus {
+                    Some(modulus) => count % modulus,
+                    None => count,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn fibonacci() -> Matrix {
+        let cells = vec![vec![1u32, 1], vec![1, 0]]
+            .into_iter()
+            .map(|row| row.into_iter().map(BigUint::from).collect())
+            .collect();
+        Matrix { cells }
+    }
+
+    #[test_case(0, 0)]
+    #[test_case(1, 1)]
+    #[test_case(10, 55)]
+    #[test_case(90, 2880067194370816120)]
+    fn test_pow(n: u64, expected: u64) {
+        let counts = [BigUint::one(), BigUint::zero()];
+
+        let result = fibonacci().pow(n, None).apply(&counts, None);
+
+        assert_eq!(BigUint::from(expected), result[1]);
+    }
+
+    #[test]
+    fn test_pow_modulo() {
+        let modulus = BigUint::from(1_000_000_007u32);
+        let counts = [BigUint::one(), BigUint::zero()];
+
+        let result = fibonacci()
+            .pow(1000, Some(&modulus))
+            .apply(&counts, Some(&modulus));
+        let exact = fibonacci().pow(1000, None).apply(&counts, None);
+
+        assert_eq!(&exact[1] % &modulus, result[1]);
+    }
+}